@@ -0,0 +1,46 @@
+use ant_colony::{
+    obstacle::Obstacles,
+    track::{Boundary, Pheromone, Tracks},
+};
+use bevy::{
+    math::Vec2,
+    tasks::{ComputeTaskPool, TaskPool},
+};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use enum_ordinalize::Ordinalize;
+
+/// One tick of pheromone decay and diffusion for two colonies, from the default world up to a
+/// 4K world at pixel resolution.
+fn update_tracks(c: &mut Criterion) {
+    ComputeTaskPool::get_or_init(TaskPool::default);
+
+    let mut group = c.benchmark_group("update_tracks");
+    for (width, height, resolution) in [
+        (1920.0, 1080.0, 4.0),
+        (1920.0, 1080.0, 2.0),
+        (1920.0, 1080.0, 1.0),
+        (3840.0, 2160.0, 1.0),
+    ] {
+        let mut tracks = Tracks::new(Vec2::new(width, height), resolution, 2);
+        let obstacles = Obstacles::new(Vec2::new(width, height), resolution);
+        let cells = tracks.width() * tracks.height() * 2 * Pheromone::VARIANT_COUNT;
+        group.throughput(Throughput::Elements(cells as u64));
+        group.bench_function(
+            BenchmarkId::from_parameter(format!("{width}x{height}@{resolution}")),
+            |b| {
+                b.iter(|| {
+                    tracks.update(
+                        [0.9998; Pheromone::VARIANT_COUNT],
+                        [0.001; Pheromone::VARIANT_COUNT],
+                        Boundary::Reflecting,
+                        &obstacles,
+                    )
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, update_tracks);
+criterion_main!(benches);
@@ -1,25 +1,104 @@
-use bevy::{math::NormedVectorSpace, prelude::*, sprite::MaterialMesh2dBundle};
+use std::collections::{BTreeMap, HashMap};
+
+use bevy::{
+    ecs::system::SystemParam, math::NormedVectorSpace, prelude::*, sprite::MaterialMesh2dBundle,
+};
 use enum_ordinalize::Ordinalize;
 use rand::prelude::*;
+use serde::{Deserialize, Serialize};
 
 use crate::{
     assets::{Colors, Meshes},
+    combat::{Armour, Attack, Health},
     config::*,
-    food::{spawn_random_food, Food},
-    nest::Nest,
-    track::Tracks,
+    crowding::Crowding,
+    food::{Food, FoodKind},
+    nest::{Colony, Nest},
+    obstacle::Obstacles,
+    predator::Predator,
+    queen::Flight,
+    rng::SimulationRng,
+    spatial::SpatialIndex,
+    task::Task,
+    track::{Pheromone, Tracks},
 };
 
 #[derive(Component)]
 pub struct Ant;
 
-#[derive(Component, Ordinalize, Clone, Copy)]
+#[derive(
+    Component, Ordinalize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
 pub enum AntKind {
     Scout,
     Worker,
+    Queen,
+    Soldier,
 }
 
-#[derive(Component)]
+impl AntKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AntKind::Scout => "scout",
+            AntKind::Worker => "worker",
+            AntKind::Queen => "queen",
+            AntKind::Soldier => "soldier",
+        }
+    }
+
+    /// Whether ants of this kind switch tasks; queens lay and soldiers defend.
+    pub fn takes_tasks(&self) -> bool {
+        matches!(self, AntKind::Scout | AntKind::Worker)
+    }
+}
+
+#[derive(Ordinalize, Clone, Copy)]
+pub enum DeathCause {
+    Starvation,
+    /// A queen landed from her nuptial flight somewhere she couldn't found a nest.
+    FailedFounding,
+    OldAge,
+    /// Killed fighting another colony.
+    Combat,
+    /// Caught by a predator.
+    Predation,
+}
+
+impl DeathCause {
+    pub fn name(&self) -> &'static str {
+        match self {
+            DeathCause::Starvation => "starvation",
+            DeathCause::FailedFounding => "failed_founding",
+            DeathCause::OldAge => "old_age",
+            DeathCause::Combat => "combat",
+            DeathCause::Predation => "predation",
+        }
+    }
+}
+
+#[derive(Event)]
+pub struct AntBorn {
+    pub kind: AntKind,
+    pub colony: Colony,
+}
+
+#[derive(Event)]
+pub struct AntDied {
+    pub entity: Entity,
+    pub colony: Colony,
+    pub cause: DeathCause,
+}
+
+#[derive(Event)]
+pub struct FoodDelivered {
+    pub nest: Entity,
+    pub colony: Colony,
+    pub kind: FoodKind,
+    pub amount: f32,
+}
+
+#[derive(Component, Clone, Serialize, Deserialize)]
 pub struct Satiation(f32);
 
 impl Satiation {
@@ -50,10 +129,31 @@ impl Satiation {
     }
 }
 
-#[derive(Component)]
+/// Seconds an ant has lived.
+#[derive(Component, Clone, Default, Serialize, Deserialize)]
+pub struct Age(f32);
+
+impl Age {
+    pub fn new(seconds: f32) -> Self {
+        Self(seconds)
+    }
+
+    pub fn seconds(&self) -> f32 {
+        self.0
+    }
+}
+
+/// The food an ant carries, which is only ever one kind at a time.
+#[derive(Component, Clone, Serialize, Deserialize)]
 pub struct HeldFood {
+    kind: FoodKind,
     amount: f32,
+    /// Units of the held kind the ant can carry at most.
     max: f32,
+    /// The most weight the ant can carry.
+    max_weight: f32,
+    /// Seconds spent so far working a pile before picking it up.
+    handling: f32,
 }
 
 impl HeldFood {
@@ -62,14 +162,35 @@ impl HeldFood {
     }
 
     pub fn full(&self) -> bool {
-        self.amount >= self.max
+        !self.empty() && self.amount >= self.max
     }
 
     pub fn amount(&self) -> f32 {
         self.amount
     }
 
-    pub fn add(&mut self, amount: f32) -> f32 {
+    pub fn kind(&self) -> FoodKind {
+        self.kind
+    }
+
+    /// Whether the ant has room for food of `kind`.
+    pub fn accepts(&self, kind: FoodKind) -> bool {
+        self.empty() || (self.kind == kind && !self.full())
+    }
+
+    pub fn handling(&self) -> bool {
+        self.handling > 0.0
+    }
+
+    /// Adds up to `amount` units of `kind`, each weighing `weight`, and returns how much fit.
+    pub fn add(&mut self, kind: FoodKind, weight: f32, amount: f32) -> f32 {
+        if !self.accepts(kind) {
+            return 0.0;
+        }
+        if self.empty() {
+            self.kind = kind;
+            self.max = self.max_weight / weight;
+        }
         let added = (self.max - self.amount).min(amount);
         self.amount += added;
         if (self.max - self.amount) < 0.0001 {
@@ -98,20 +219,32 @@ pub fn spawn_ant(
     y: f32,
     rotation: f32,
     kind: AntKind,
+    colony: Colony,
 ) -> Entity {
+    let kind_config = &simulation_config.ant_kinds[kind];
     commands
         .spawn((
             Ant,
             Satiation(1.0),
+            Age::default(),
+            Health::new(kind_config.health),
+            Attack(kind_config.attack),
+            Armour(kind_config.armour),
+            Crowding::default(),
             HeldFood {
+                kind: FoodKind::default(),
                 amount: 0.0,
                 max: simulation_config.ant_max_carry,
+                max_weight: simulation_config.ant_max_carry,
+                handling: 0.0,
             },
             SpatialBundle::from_transform(
                 Transform::from_translation(Vec3::new(x, y, 0.0))
                     .mul_transform(Transform::from_rotation(Quat::from_rotation_z(rotation))),
             ),
             kind,
+            Task::initial(kind),
+            colony,
         ))
         .id()
 }
@@ -123,10 +256,7 @@ pub fn setup_ant_rendering(
     new_ants: Query<(Entity, &AntKind), Added<Ant>>,
 ) {
     for (entity, kind) in new_ants.iter() {
-        let color = match kind {
-            AntKind::Scout => colors.ant_scout.clone(),
-            AntKind::Worker => colors.ant_worker.clone(),
-        };
+        let color = colors.ant(*kind);
         commands.entity(entity).with_children(|parent| {
             let head_y = 3.0 * ANT_SEGMENT_RADIUS / 2.0;
             let antenna_y = head_y + ANT_SEGMENT_RADIUS;
@@ -166,7 +296,7 @@ pub fn setup_ant_rendering(
                 CarriedFood,
                 MaterialMesh2dBundle {
                     mesh: meshes.food.clone(),
-                    material: colors.food.clone(),
+                    material: colors.food(FoodKind::default()),
                     transform: Transform::from_translation(Vec3::new(
                         0.0,
                         head_y + ANT_SEGMENT_RADIUS,
@@ -180,44 +310,188 @@ pub fn setup_ant_rendering(
     }
 }
 
-pub fn decay_satiation(mut satiations: Query<&mut Satiation>) {
-    for mut satiation in satiations.iter_mut() {
-        satiation.remove(ANT_ENERGY_LOSS_RATE * FIXED_DELTA_TIME);
+pub fn decay_satiation(
+    simulation_config: Res<SimulationConfig>,
+    mut satiations: Query<(&mut Satiation, &AntKind, &Task)>,
+) {
+    let delta_time = simulation_config.fixed_delta_time();
+    let energy_loss = simulation_config.ant_energy_loss_rate * delta_time;
+    let queen_energy_loss = simulation_config.queen.energy_loss_rate * delta_time;
+    let rest_energy_loss = energy_loss * simulation_config.tasks.rest_energy_factor;
+    for (mut satiation, kind, task) in satiations.iter_mut() {
+        satiation.remove(match (kind, task) {
+            (AntKind::Queen, _) => queen_energy_loss,
+            (_, Task::Rest) => rest_energy_loss,
+            _ => energy_loss,
+        });
+    }
+}
+
+/// Ages ants, lets those past their kind's lifespan die and changes the kinds of others as the
+/// configured rules say.
+pub fn age_ants(
+    simulation_config: Res<SimulationConfig>,
+    mut ants: Query<(Entity, &mut Age, &mut AntKind, &Satiation, &Colony)>,
+    mut deaths: EventWriter<AntDied>,
+) {
+    let delta_time = simulation_config.fixed_delta_time();
+    let mut counts: BTreeMap<Colony, [u32; AntKind::VARIANT_COUNT]> = BTreeMap::new();
+    for (entity, mut age, kind, satiation, colony) in ants.iter_mut() {
+        age.0 += delta_time;
+        let lifespan = simulation_config.ant_kinds[*kind].lifespan;
+        // Starved ants are already dying.
+        if satiation.empty() {
+            continue;
+        }
+        if lifespan > 0.0 && age.0 >= lifespan {
+            deaths.send(AntDied {
+                entity,
+                colony: *colony,
+                cause: DeathCause::OldAge,
+            });
+            continue;
+        }
+        counts.entry(*colony).or_default()[kind.ordinal() as usize] += 1;
+    }
+
+    for change in &simulation_config.kind_changes {
+        for (_, age, mut kind, satiation, colony) in ants.iter_mut() {
+            if *kind != change.from || age.0 < change.min_age || satiation.empty() {
+                continue;
+            }
+            let lifespan = simulation_config.ant_kinds[*kind].lifespan;
+            if lifespan > 0.0 && age.0 >= lifespan {
+                continue;
+            }
+            let counts = counts.get_mut(colony).unwrap();
+            let total: u32 = counts.iter().sum();
+            if counts[change.to.ordinal() as usize] as f32 >= change.max_share * total as f32 {
+                continue;
+            }
+            counts[change.from.ordinal() as usize] -= 1;
+            counts[change.to.ordinal() as usize] += 1;
+            *kind = change.to;
+        }
     }
 }
 
-pub fn eat_held_food(mut eaters: Query<(&mut HeldFood, &mut Satiation)>) {
+/// Recolors ants whose kind changed after they were drawn.
+pub fn update_ant_kind_color(
+    colors: Res<Colors>,
+    ants: Query<(&AntKind, &Children), Changed<AntKind>>,
+    mut segments: Query<&mut Handle<ColorMaterial>, Without<CarriedFood>>,
+) {
+    for (kind, children) in ants.iter() {
+        for child in children.iter() {
+            if let Ok(mut material) = segments.get_mut(*child) {
+                *material = colors.ant(*kind);
+            }
+        }
+    }
+}
+
+pub fn eat_held_food(
+    simulation_config: Res<SimulationConfig>,
+    mut eaters: Query<(&mut HeldFood, &mut Satiation)>,
+) {
+    let delta_time = simulation_config.fixed_delta_time();
     for (mut held_food, mut satiation) in eaters.iter_mut() {
-        let eats = held_food.remove((ANT_MAX_ENERGY - satiation.amount()) * 0.1 * FIXED_DELTA_TIME);
-        satiation.add(eats);
+        if held_food.empty() {
+            continue;
+        }
+        let energy_per_unit = simulation_config.food_kinds[held_food.kind()].energy;
+        let hunger = (ANT_MAX_ENERGY - satiation.amount()) * 0.1 * delta_time;
+        let eats = held_food.remove(hunger / energy_per_unit);
+        satiation.add(eats * energy_per_unit);
     }
 }
 
-pub fn starve(mut commands: Commands, satiations: Query<(Entity, &Satiation)>) {
-    for (entity, satiation) in satiations.iter() {
+pub fn starve(satiations: Query<(Entity, &Satiation, &Colony)>, mut deaths: EventWriter<AntDied>) {
+    for (entity, satiation, colony) in satiations.iter() {
         if satiation.empty() {
-            commands.entity(entity).despawn_recursive();
+            deaths.send(AntDied {
+                entity,
+                colony: *colony,
+                cause: DeathCause::Starvation,
+            });
         }
     }
 }
 
-pub fn walk_ants(mut ants: Query<&mut Transform, With<Ant>>) {
+pub fn remove_dead_ants(mut commands: Commands, mut deaths: EventReader<AntDied>) {
+    for death in deaths.read() {
+        if let Some(entity) = commands.get_entity(death.entity) {
+            entity.despawn_recursive();
+        }
+    }
+}
+
+/// What an ant walks by: where it is, what it's doing and who is in its way.
+type WalkerData = (
+    &'static mut Transform,
+    &'static HeldFood,
+    &'static AntKind,
+    &'static Task,
+    &'static Colony,
+    &'static Crowding,
+);
+
+pub fn walk_ants(
+    simulation_config: Res<SimulationConfig>,
+    obstacles: Res<Obstacles>,
+    mut ants: Query<WalkerData, With<Ant>>,
+    nests: Query<(&Transform, &Colony, &Nest), Without<Ant>>,
+) {
     let min_distance_from_edge = ANT_SEGMENT_RADIUS * 2.0 * 1.5;
+    let half_width = simulation_config.world_width / 2.0;
+    let half_height = simulation_config.world_height / 2.0;
+    let step = simulation_config.ant_speed * simulation_config.fixed_delta_time();
 
-    for mut transform in ants.iter_mut() {
-        let forward = transform.up();
-        transform.translation += forward * ANT_SPEED * FIXED_DELTA_TIME;
+    for (mut transform, held_food, kind, task, colony, crowding) in ants.iter_mut() {
+        // Ants stay put while they work a pile; queens move only on nuptial flights.
+        if held_food.handling() || *kind == AntKind::Queen {
+            continue;
+        }
+        // Resting ants and nurses stay put once they're home.
+        let home = task.in_nest()
+            && nests.iter().any(|(nest_transform, nest_colony, _)| {
+                nest_colony == colony
+                    && nest_transform
+                        .translation
+                        .xy()
+                        .distance(transform.translation.xy())
+                        < simulation_config.nest_radius
+            });
+        if home {
+            continue;
+        }
+        let position = transform.translation.xy();
+        // Ants in the way hold an ant up, so busy trails jam.
+        let offset = transform.up().xy() * step * crowding.speed_factor(&simulation_config);
+        // Slide along an obstacle when the full step would enter it. Ants already inside one
+        // (such as after a resize) are let out.
+        let clear = |to: Vec2| obstacles.is_blocked(position) || !obstacles.is_blocked(to);
+        if let Some(to) = [
+            position + offset,
+            position + Vec2::new(offset.x, 0.0),
+            position + Vec2::new(0.0, offset.y),
+        ]
+        .into_iter()
+        .find(|to| clear(*to))
+        {
+            transform.translation = to.extend(transform.translation.z);
+        }
 
-        if transform.translation.x < -WORLD_WIDTH / 2.0 + min_distance_from_edge {
-            transform.translation.x = -WORLD_WIDTH / 2.0 + min_distance_from_edge;
-        } else if transform.translation.x > WORLD_WIDTH / 2.0 - min_distance_from_edge {
-            transform.translation.x = WORLD_WIDTH / 2.0 - min_distance_from_edge;
+        if transform.translation.x < -half_width + min_distance_from_edge {
+            transform.translation.x = -half_width + min_distance_from_edge;
+        } else if transform.translation.x > half_width - min_distance_from_edge {
+            transform.translation.x = half_width - min_distance_from_edge;
         }
 
-        if transform.translation.y < -WORLD_HEIGHT / 2.0 + min_distance_from_edge {
-            transform.translation.y = -WORLD_HEIGHT / 2.0 + min_distance_from_edge;
-        } else if transform.translation.y > WORLD_HEIGHT / 2.0 - min_distance_from_edge {
-            transform.translation.y = WORLD_HEIGHT / 2.0 - min_distance_from_edge;
+        if transform.translation.y < -half_height + min_distance_from_edge {
+            transform.translation.y = -half_height + min_distance_from_edge;
+        } else if transform.translation.y > half_height - min_distance_from_edge {
+            transform.translation.y = half_height - min_distance_from_edge;
         }
     }
 }
@@ -226,20 +500,60 @@ enum AntGoal {
     Scout,
     Food,
     Nest,
+    /// Towards ants of other colonies, wandering like a scout when there are none.
+    Enemy,
 }
 
+/// What ants can sense of the world around them.
+#[derive(SystemParam)]
+pub struct Surroundings<'w, 's> {
+    tracks: Query<'w, 's, &'static Tracks>,
+    food_index: Res<'w, SpatialIndex<Food>>,
+    food: Query<'w, 's, &'static Food>,
+    nests: Query<'w, 's, (&'static Transform, &'static Colony, &'static Nest), Without<Ant>>,
+    obstacles: Res<'w, Obstacles>,
+    ant_index: Res<'w, SpatialIndex<Ant>>,
+    /// Ants that can be fought, which leaves out queens on their nuptial flight.
+    combatants: Query<'w, 's, &'static Colony, (With<Ant>, Without<Flight>)>,
+    predators: Query<'w, 's, &'static Transform, (With<Predator>, Without<Ant>)>,
+}
+
+/// What an ant steers by: where it is and what it's after.
+type SteeringData = (
+    &'static mut Transform,
+    &'static Satiation,
+    &'static HeldFood,
+    &'static AntKind,
+    &'static Task,
+    &'static Colony,
+);
+
 pub fn rotate_ants(
     simulation_config: Res<SimulationConfig>,
-    mut ants: Query<(&mut Transform, &Satiation, &HeldFood, &AntKind), With<Ant>>,
-    tracks: Query<&Tracks>,
-    food: Query<(Entity, &Food, &Transform), Without<Ant>>,
-    nests: Query<&Transform, (With<Nest>, Without<Ant>)>,
+    mut rng: ResMut<SimulationRng>,
+    mut ants: Query<SteeringData, With<Ant>>,
+    surroundings: Surroundings,
 ) {
-    let mut rng = rand::thread_rng();
-    let nest_transform = nests.single();
+    let Surroundings {
+        tracks,
+        food_index,
+        food,
+        nests,
+        obstacles,
+        ant_index,
+        combatants,
+        predators,
+    } = &surroundings;
     let tracks = tracks.single();
+    let colony_nests: HashMap<Colony, Vec2> = nests
+        .iter()
+        .map(|(transform, colony, _)| (*colony, transform.translation.xy()))
+        .collect();
     let ant_sense_distance = simulation_config.ant_sense_distance;
     let ant_sense_radius = simulation_config.ant_sense_radius;
+    let nest_radius = simulation_config.nest_radius;
+    let half_width = simulation_config.world_width / 2.0;
+    let half_height = simulation_config.world_height / 2.0;
 
     let sense_offsets = [
         Vec3::Y * ant_sense_distance,
@@ -249,24 +563,60 @@ pub fn rotate_ants(
             * ant_sense_distance,
     ];
 
-    for (mut ant_transform, satiation, held_food, ant_kind) in ants.iter_mut() {
+    for (mut ant_transform, satiation, held_food, ant_kind, task, colony) in ants.iter_mut() {
+        // Queens keep to the nest, or fly straight while on a nuptial flight.
+        if *ant_kind == AntKind::Queen {
+            continue;
+        }
+        let nest_position = colony_nests.get(colony);
         let forward = ant_transform.up();
 
         let goal = if satiation.amount() < ANT_MAX_ENERGY * 0.5 {
             AntGoal::Nest
         } else {
-            match ant_kind {
-                AntKind::Scout => AntGoal::Scout,
-                AntKind::Worker => {
+            match task {
+                Task::Scout => AntGoal::Scout,
+                Task::Forage => {
                     if held_food.empty() {
                         AntGoal::Food
                     } else {
                         AntGoal::Nest
                     }
                 }
+                Task::Rest | Task::NestWork => AntGoal::Nest,
+                Task::Defend => {
+                    let patrolling = nest_position.is_none_or(|nest_position| {
+                        nest_position.distance(ant_transform.translation.xy())
+                            < simulation_config.combat.patrol_radius
+                    });
+                    // Alarm draws soldiers out past their patrol.
+                    let alarmed = tracks
+                        .within_circle(*colony, ant_transform.translation.xy(), ant_sense_distance)
+                        .any(|track| track[Pheromone::Alarm] > 0.001);
+                    if patrolling || alarmed {
+                        AntGoal::Enemy
+                    } else {
+                        AntGoal::Nest
+                    }
+                }
             }
         };
 
+        // Scouting ants are attracted to low pheromone concentrations.
+        let unexplored = |sense_center: Vec2| {
+            1.0 - tracks
+                .within_circle(*colony, sense_center, ant_sense_radius)
+                .map(|track| track[Pheromone::Food].max(track[Pheromone::Nest]))
+                .sum::<f32>()
+        };
+        let alarm = |sense_center: Vec2| {
+            tracks
+                .within_circle(*colony, sense_center, ant_sense_radius)
+                .map(|track| track[Pheromone::Alarm])
+                .sum::<f32>()
+        };
+        let alarm_config = &simulation_config.alarm;
+
         let mut direction: Vec2 = sense_offsets
             .iter()
             .map(|sense_offset| {
@@ -275,50 +625,80 @@ pub fn rotate_ants(
 
                 // gizmos.circle_2d(sense_center, ANT_SENSE_RADIUS, WHITE);
 
+                // Nothing is sensed through obstacles, and ants steer away from them.
+                if !obstacles.line_clear(ant_transform.translation.xy(), sense_center) {
+                    return -10.0 * (sense_center - ant_transform.translation.xy());
+                }
+                let blocked = obstacles.blocked_fraction(sense_center, ant_sense_radius);
+                if blocked > 0.0 {
+                    return -10.0 * blocked * (sense_center - ant_transform.translation.xy());
+                }
+
                 let weight = match goal {
                     AntGoal::Food => {
-                        let sensed_food = food.iter().find(|(_, food, food_transform)| {
-                            food_transform.translation.xy().distance(sense_center)
-                                < ant_sense_radius + food.radius()
-                        });
-                        if sensed_food.is_some() {
+                        let sensed_food = food_index
+                            .within(sense_center, ant_sense_radius)
+                            .any(|(entity, _)| food.get(entity).is_ok_and(|food| !food.empty()));
+                        if sensed_food {
                             10.0
                         } else {
                             tracks
-                                .within_circle(sense_center, ant_sense_radius)
-                                .map(|track| track.food)
+                                .within_circle(*colony, sense_center, ant_sense_radius)
+                                .map(|track| track[Pheromone::Food])
                                 .sum::<f32>()
                         }
                     }
                     AntGoal::Nest => {
-                        let sensed_nest = nest_transform.translation.xy().distance(sense_center)
-                            < ant_sense_radius + NEST_RADIUS;
+                        let sensed_nest = nest_position.is_some_and(|nest_position| {
+                            nest_position.distance(sense_center) < ant_sense_radius + nest_radius
+                        });
                         if sensed_nest {
                             10.0
                         } else {
                             tracks
-                                .within_circle(sense_center, ant_sense_radius)
-                                .map(|track| track.nest)
+                                .within_circle(*colony, sense_center, ant_sense_radius)
+                                .map(|track| track[Pheromone::Nest])
                                 .sum::<f32>()
                         }
                     }
-                    AntGoal::Scout => {
-                        // Scout ants are attracted to low pheromone concentrations
-                        1.0 - tracks
-                            .within_circle(sense_center, ant_sense_radius)
-                            .map(|track| track.food.max(track.nest))
-                            .sum::<f32>()
+                    AntGoal::Scout => unexplored(sense_center),
+                    AntGoal::Enemy => {
+                        let sensed_enemy =
+                            ant_index
+                                .within(sense_center, ant_sense_radius)
+                                .any(|(entity, _)| {
+                                    combatants
+                                        .get(entity)
+                                        .is_ok_and(|other_colony| other_colony != colony)
+                                })
+                                || predators.iter().any(|predator| {
+                                    predator.translation.xy().distance(sense_center)
+                                        < ant_sense_radius
+                                });
+                        if sensed_enemy {
+                            10.0
+                        } else {
+                            unexplored(sense_center)
+                        }
                     }
                 };
+                // Alarm draws soldiers in, and foragers and scouts keep clear of it.
+                let weight = match goal {
+                    AntGoal::Enemy => weight + alarm_config.attraction * alarm(sense_center),
+                    AntGoal::Food | AntGoal::Scout => {
+                        weight / (1.0 + alarm_config.repulsion * alarm(sense_center))
+                    }
+                    AntGoal::Nest => weight,
+                };
 
                 weight.max(0.000001) * (sense_center - ant_transform.translation.xy())
             })
             .sum::<Vec2>()
-            .normalize();
+            .normalize_or_zero();
 
         let min_distance_fron_edge = 10.0;
         let soft_min_distance_from_edge = 50.0;
-        let distance = ant_transform.translation.x.distance(WORLD_WIDTH / 2.0);
+        let distance = ant_transform.translation.x.distance(half_width);
         if distance < soft_min_distance_from_edge {
             direction.x -= 2.0
                 * (1.0
@@ -326,7 +706,7 @@ pub fn rotate_ants(
                         / (soft_min_distance_from_edge - min_distance_fron_edge));
         }
 
-        let distance = ant_transform.translation.x.distance(-WORLD_WIDTH / 2.0);
+        let distance = ant_transform.translation.x.distance(-half_width);
         if distance < soft_min_distance_from_edge {
             direction.x += 2.0
                 * (1.0
@@ -334,7 +714,7 @@ pub fn rotate_ants(
                         / (soft_min_distance_from_edge - min_distance_fron_edge));
         }
 
-        let distance = ant_transform.translation.y.distance(WORLD_HEIGHT / 2.0);
+        let distance = ant_transform.translation.y.distance(half_height);
         if distance < soft_min_distance_from_edge {
             direction.y -= 2.0
                 * (1.0
@@ -342,7 +722,7 @@ pub fn rotate_ants(
                         / (soft_min_distance_from_edge - min_distance_fron_edge));
         }
 
-        let distance = ant_transform.translation.y.distance(-WORLD_HEIGHT / 2.0);
+        let distance = ant_transform.translation.y.distance(-half_height);
         if distance < soft_min_distance_from_edge {
             direction.y += 2.0
                 * (1.0
@@ -361,7 +741,7 @@ pub fn rotate_ants(
             rng.gen_range(-std::f32::consts::PI..std::f32::consts::PI)
         };
 
-        let max_turn = ANT_ROTATION_SPEED * FIXED_DELTA_TIME;
+        let max_turn = simulation_config.ant_rotation_speed * simulation_config.fixed_delta_time();
         let angle = angle.clamp(-max_turn, max_turn);
 
         // We need to normalize the rotation quaternion because it can drift over time due to floating point errors
@@ -370,91 +750,163 @@ pub fn rotate_ants(
     }
 }
 
+/// Lays alarm pheromone for an ant of `colony` in trouble at `position`.
+pub fn raise_alarm(
+    tracks: &mut Tracks,
+    simulation_config: &SimulationConfig,
+    colony: Colony,
+    position: Vec2,
+) {
+    let concentration =
+        simulation_config.alarm.concentration * simulation_config.fixed_delta_time();
+    let cap = simulation_config.pheromones[Pheromone::Alarm].cap;
+    tracks.within_circle_mut(colony, Pheromone::Alarm, position, TRACK_RADIUS, |value| {
+        *value = (*value + concentration).min(cap)
+    });
+}
+
 pub fn emit_ant_pheromones(
     simulation_config: Res<SimulationConfig>,
-    ants: Query<(&Transform, &HeldFood), With<Ant>>,
+    ants: Query<(&Transform, &Satiation, &HeldFood, &AntKind, &Colony), With<Ant>>,
+    ant_index: Res<SpatialIndex<Ant>>,
+    combatants: Query<&Colony, (With<Ant>, Without<Flight>)>,
+    predators: Query<&Transform, With<Predator>>,
     mut tracks: Query<&mut Tracks>,
 ) {
     let mut tracks = tracks.single_mut();
-    for (ant_transform, held_food) in ants.iter() {
-        tracks.within_circle_mut(ant_transform.translation.xy(), TRACK_RADIUS, |track| {
-            if !held_food.empty() {
-                track.food += simulation_config.ant_track_concentration * FIXED_DELTA_TIME;
-                track.food = track.food.min(1.0);
-            } else {
-                track.nest += simulation_config.ant_track_concentration * FIXED_DELTA_TIME;
-                track.nest = track.nest.min(1.0);
-            }
+    let concentration =
+        simulation_config.ant_track_concentration * simulation_config.fixed_delta_time();
+    let hungry = simulation_config.alarm.hunger * ANT_MAX_ENERGY;
+    for (ant_transform, satiation, held_food, kind, colony) in ants.iter() {
+        if *kind == AntKind::Queen {
+            continue;
+        }
+        let position = ant_transform.translation.xy();
+        let pheromone = if held_food.empty() {
+            Pheromone::Nest
+        } else {
+            Pheromone::Food
+        };
+        let cap = simulation_config.pheromones[pheromone].cap;
+        tracks.within_circle_mut(*colony, pheromone, position, TRACK_RADIUS, |value| {
+            *value = (*value + concentration).min(cap)
         });
+
+        // Ants under attack raise the alarm as they're hit, in `fight`.
+        let sense_distance = simulation_config.ant_sense_distance;
+        let threatened = ant_index
+            .within(position, sense_distance)
+            .any(|(entity, _)| {
+                combatants
+                    .get(entity)
+                    .is_ok_and(|other_colony| other_colony != colony)
+            })
+            || predators
+                .iter()
+                .any(|predator| predator.translation.xy().distance(position) < sense_distance);
+        if threatened || satiation.amount() < hungry {
+            raise_alarm(&mut tracks, &simulation_config, *colony, position);
+        }
     }
 }
 
 pub fn pick_up_food(
-    mut commands: Commands,
-    mut ants: Query<(&Transform, &mut HeldFood), With<Ant>>,
-    mut food: Query<(Entity, &mut Food, &Transform), Without<Ant>>,
+    simulation_config: Res<SimulationConfig>,
+    food_index: Res<SpatialIndex<Food>>,
+    mut ants: Query<(&Transform, &mut HeldFood, &AntKind), With<Ant>>,
+    mut food: Query<&mut Food, Without<Ant>>,
 ) {
-    for (ant_transform, mut held_food) in ants.iter_mut() {
-        if held_food.full() {
+    for (ant_transform, mut held_food, kind) in ants.iter_mut() {
+        // Only ants that forage or scout pick up food.
+        if held_food.full() || !kind.takes_tasks() {
             continue;
         }
 
-        let nearby_food = food.iter_mut().find(|(_, food, transform)| {
-            transform
-                .translation
-                .xy()
-                .distance(ant_transform.translation.xy())
-                < ANT_SEGMENT_RADIUS * 1.5 + food.radius()
-        });
+        // Emptied piles stay indexed until the food policy clears them out, so skip them.
+        let nearby_food = food_index.nearest_within(
+            ant_transform.translation.xy(),
+            ANT_SEGMENT_RADIUS * 1.5,
+            |entity| {
+                food.get(entity)
+                    .is_ok_and(|food| !food.empty() && held_food.accepts(food.kind()))
+            },
+        );
 
-        if let Some((entity, mut food, _)) = nearby_food {
-            let took = held_food.add(food.amount());
-            food.remove(took);
-            if food.empty() {
-                commands.entity(entity).despawn();
-                spawn_random_food(&mut commands);
+        let Some(entity) = nearby_food else {
+            // Someone else finished the pile first.
+            if held_food.handling() {
+                held_food.handling = 0.0;
             }
+            continue;
+        };
+        let mut food = food.get_mut(entity).unwrap();
+        let kind = simulation_config.food_kinds[food.kind()];
+        held_food.handling += simulation_config.fixed_delta_time();
+        if held_food.handling < kind.pickup_time {
+            continue;
         }
+        held_food.handling = 0.0;
+        let took = held_food.add(food.kind(), kind.weight, food.amount());
+        food.remove(took);
     }
 }
 
 pub fn deposit_food(
-    mut ants: Query<(&Transform, &mut HeldFood), With<Ant>>,
-    mut nests: Query<(&mut Nest, &Transform), Without<Ant>>,
+    mut ants: Query<(&Transform, &mut HeldFood, &Colony), With<Ant>>,
+    nest_index: Res<SpatialIndex<Nest>>,
+    mut nests: Query<(&mut Nest, &Colony), Without<Ant>>,
+    mut deliveries: EventWriter<FoodDelivered>,
 ) {
-    let (mut nest, nest_transform) = nests.single_mut();
-
-    for (ant_transform, mut held_food) in ants.iter_mut() {
+    for (ant_transform, mut held_food, colony) in ants.iter_mut() {
         if held_food.empty() {
             continue;
         }
 
-        let nest_distance = ant_transform
-            .translation
-            .xy()
-            .distance(nest_transform.translation.xy());
+        let home_nest = nest_index.nearest_within(
+            ant_transform.translation.xy(),
+            ANT_SEGMENT_RADIUS * 1.5,
+            |entity| {
+                nests
+                    .get(entity)
+                    .is_ok_and(|(_, nest_colony)| nest_colony == colony)
+            },
+        );
 
-        if nest_distance < ANT_SEGMENT_RADIUS * 1.5 + NEST_RADIUS {
+        if let Some(nest_entity) = home_nest {
+            let (mut nest, _) = nests.get_mut(nest_entity).unwrap();
             let amount = held_food.amount();
-            nest.food += amount;
+            let kind = held_food.kind();
+            nest.food[kind] += amount;
             held_food.remove(amount);
+            deliveries.send(FoodDelivered {
+                nest: nest_entity,
+                colony: *colony,
+                kind,
+                amount,
+            });
         }
     }
 }
 
 pub fn eat_nest_food(
-    mut nests: Query<(&mut Nest, &Transform)>,
-    mut satiations: Query<(&mut Satiation, &Transform)>,
+    simulation_config: Res<SimulationConfig>,
+    mut nests: Query<(&mut Nest, &Transform, &Colony)>,
+    mut satiations: Query<(&mut Satiation, &Transform, &Colony)>,
 ) {
-    for (mut nest, nest_transform) in nests.iter_mut() {
-        for (mut satiation, satiation_transform) in satiations.iter_mut() {
+    for (mut nest, nest_transform, nest_colony) in nests.iter_mut() {
+        for (mut satiation, satiation_transform, colony) in satiations.iter_mut() {
+            if colony != nest_colony {
+                continue;
+            }
             let distance = satiation_transform
                 .translation
                 .xy()
                 .distance(nest_transform.translation.xy());
-            if distance < ANT_SEGMENT_RADIUS * 1.5 + NEST_RADIUS {
-                let eats = nest.food.min(ANT_MAX_ENERGY - satiation.amount());
-                nest.food -= eats;
+            if distance < ANT_SEGMENT_RADIUS * 1.5 + simulation_config.nest_radius {
+                let eats = nest.food.take_energy(
+                    ANT_MAX_ENERGY - satiation.amount(),
+                    &simulation_config.food_kinds,
+                );
                 satiation.add(eats);
             }
         }
@@ -462,18 +914,192 @@ pub fn eat_nest_food(
 }
 
 pub fn update_ant_holding_food(
+    colors: Res<Colors>,
     held_food_query: Query<(&HeldFood, &Children), Changed<HeldFood>>,
-    mut carried_food_query: Query<(Entity, &CarriedFood, &mut Visibility)>,
+    mut carried_food_query: Query<(&mut Visibility, &mut Handle<ColorMaterial>), With<CarriedFood>>,
 ) {
     for (held_food, children) in held_food_query.iter() {
         for child in children.iter() {
-            if let Ok((_, _, mut visibility)) = carried_food_query.get_mut(*child) {
+            if let Ok((mut visibility, mut material)) = carried_food_query.get_mut(*child) {
                 *visibility = if !held_food.empty() {
                     Visibility::Inherited
                 } else {
                     Visibility::Hidden
                 };
+                *material = colors.food(held_food.kind());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+    use crate::nest::spawn_nest;
+
+    /// Turns one ant of colony 0 at the origin, facing up, once with the given pheromone laid
+    /// around each spot, and returns the way it faces afterwards. Its nest is far off to the
+    /// left, well past `patrol_radius`.
+    fn heading_after_turning(kind: AntKind, marks: &[(Pheromone, Vec2)]) -> Vec2 {
+        let mut world = World::new();
+        let simulation_config = SimulationConfig {
+            ticks_per_second: 1.0,
+            ..default()
+        };
+        let mut tracks = Tracks::new(
+            Vec2::new(
+                simulation_config.world_width,
+                simulation_config.world_height,
+            ),
+            simulation_config.track_resolution,
+            1,
+        );
+        for &(pheromone, position) in marks {
+            tracks.within_circle_mut(Colony(0), pheromone, position, TRACK_RADIUS, |value| {
+                *value = 1.0
+            });
+        }
+        world.spawn(tracks);
+        world.insert_resource(SpatialIndex::<Food>::from_config(&simulation_config));
+        world.insert_resource(SpatialIndex::<Ant>::from_config(&simulation_config));
+        world.insert_resource(Obstacles::from_config(&simulation_config));
+        world.insert_resource(simulation_config);
+        world.insert_resource(SimulationRng::new(Some(1)));
+        let ant = world.run_system_once(
+            move |mut commands: Commands, simulation_config: Res<SimulationConfig>| {
+                spawn_nest(&mut commands, -200.0, 0.0, Colony(0));
+                spawn_ant(
+                    &mut commands,
+                    &simulation_config,
+                    0.0,
+                    0.0,
+                    0.0,
+                    kind,
+                    Colony(0),
+                )
+            },
+        );
+
+        world.run_system_once(rotate_ants);
+        world.get::<Transform>(ant).unwrap().up().xy()
+    }
+
+    /// Spots seen by only the left or only the right of an ant's three sensors.
+    const LEFT: Vec2 = Vec2::new(-8.0, 8.0);
+    const RIGHT: Vec2 = Vec2::new(8.0, 8.0);
+
+    #[test]
+    fn alarm_draws_soldiers_past_their_patrol() {
+        // Out past its patrol a soldier heads home along the nest trail, unless it senses the
+        // alarm. Both turns get the same random wobble, so only the pull differs.
+        let calm = heading_after_turning(AntKind::Soldier, &[(Pheromone::Nest, LEFT)]);
+        let alarmed = heading_after_turning(
+            AntKind::Soldier,
+            &[(Pheromone::Nest, LEFT), (Pheromone::Alarm, RIGHT)],
+        );
+        assert!(calm.x < 0.0, "{calm}");
+        assert!(alarmed.x > 0.0, "{alarmed}");
+    }
+
+    #[test]
+    fn alarm_keeps_foragers_away() {
+        let trail = [(Pheromone::Food, LEFT), (Pheromone::Food, RIGHT)];
+        let calm = heading_after_turning(AntKind::Worker, &trail);
+        let alarmed = heading_after_turning(
+            AntKind::Worker,
+            &[trail[0], trail[1], (Pheromone::Alarm, RIGHT)],
+        );
+        assert!(alarmed.x < calm.x - 0.3, "{calm} {alarmed}");
+    }
+
+    /// A world stepping one second per run with the given kind changes, and a worker of each
+    /// colony listed.
+    fn ageing_world(kind_changes: Vec<KindChange>, colonies: &[u32]) -> World {
+        let mut world = World::new();
+        world.insert_resource(SimulationConfig {
+            ticks_per_second: 1.0,
+            kind_changes,
+            ..default()
+        });
+        world.init_resource::<Events<AntDied>>();
+        for &colony in colonies {
+            world.run_system_once(
+                move |mut commands: Commands, simulation_config: Res<SimulationConfig>| {
+                    spawn_ant(
+                        &mut commands,
+                        &simulation_config,
+                        0.0,
+                        0.0,
+                        0.0,
+                        AntKind::Worker,
+                        Colony(colony),
+                    );
+                },
+            );
+        }
+        world
+    }
+
+    /// The number of scouts in each of colonies 0 and 1.
+    fn scouts(world: &mut World) -> [usize; 2] {
+        let mut scouts = [0; 2];
+        for (kind, colony) in world.query::<(&AntKind, &Colony)>().iter(world) {
+            if *kind == AntKind::Scout {
+                scouts[colony.0 as usize] += 1;
             }
         }
+        scouts
+    }
+
+    #[test]
+    fn kind_changes_stop_at_their_share_of_each_colony() {
+        let mut world = ageing_world(
+            vec![KindChange {
+                from: AntKind::Worker,
+                to: AntKind::Scout,
+                min_age: 2.0,
+                max_share: 0.3,
+            }],
+            &[[0; 10], [1; 10]].concat(),
+        );
+
+        world.run_system_once(age_ants);
+        assert_eq!(scouts(&mut world), [0, 0]);
+        for _ in 0..3 {
+            world.run_system_once(age_ants);
+            assert_eq!(scouts(&mut world), [3, 3]);
+        }
+    }
+
+    #[test]
+    fn starving_ants_neither_die_of_age_nor_change() {
+        let mut world = ageing_world(
+            vec![KindChange {
+                from: AntKind::Worker,
+                to: AntKind::Scout,
+                min_age: 0.0,
+                max_share: 1.0,
+            }],
+            &[0, 0],
+        );
+        let lifespan = world.resource::<SimulationConfig>().ant_kinds[AntKind::Worker].lifespan;
+        let mut ants = world.query::<(Entity, &mut Age, &mut Satiation)>();
+        let mut ants = ants.iter_mut(&mut world);
+        let (old, mut age, _) = ants.next().unwrap();
+        age.0 = lifespan;
+        let (starving, mut age, mut satiation) = ants.next().unwrap();
+        age.0 = lifespan;
+        satiation.0 = 0.0;
+
+        world.run_system_once(age_ants);
+        let deaths: Vec<_> = world
+            .resource_mut::<Events<AntDied>>()
+            .drain()
+            .map(|death| (death.entity, death.cause.name()))
+            .collect();
+        assert_eq!(deaths, [(old, DeathCause::OldAge.name())]);
+        assert!(*world.get::<AntKind>(starving).unwrap() == AntKind::Worker);
     }
 }
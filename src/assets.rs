@@ -1,14 +1,20 @@
 use bevy::{prelude::*, sprite::Mesh2dHandle};
+use enum_ordinalize::Ordinalize;
 
-use crate::config::{
-    ANT_ANTENNA_RADIUS, ANT_COLOR, ANT_SEGMENT_RADIUS, DIRT_COLOR, FOOD_COLOR, NEST_COLOR,
-    WORLD_HEIGHT, WORLD_WIDTH,
+use crate::{
+    ant::AntKind,
+    config::{
+        SimulationConfig, ANT_ANTENNA_RADIUS, ANT_COLOR, ANT_SEGMENT_RADIUS, DIRT_COLOR, NEST_COLOR,
+    },
+    food::FoodKind,
+    predator::PredatorKind,
 };
 
 #[derive(Resource)]
 pub struct Meshes {
     pub food: Mesh2dHandle,
     pub nest: Mesh2dHandle,
+    pub predator: Mesh2dHandle,
     pub ant_antenna: Mesh2dHandle,
     pub ant_segment: Mesh2dHandle,
     pub dirt: Mesh2dHandle,
@@ -16,17 +22,26 @@ pub struct Meshes {
 
 impl FromWorld for Meshes {
     fn from_world(world: &mut World) -> Self {
+        let simulation_config = world.resource::<SimulationConfig>();
+        let nest_radius = simulation_config.nest_radius;
+        let dirt = Rectangle::new(
+            simulation_config.world_width,
+            simulation_config.world_height,
+        );
         let mut meshes = world.resource_mut::<Assets<Mesh>>();
         Self {
             food: Mesh2dHandle(meshes.add(Circle { radius: 1.0 })),
-            nest: Mesh2dHandle(meshes.add(Circle { radius: 10.0 })),
+            nest: Mesh2dHandle(meshes.add(Circle {
+                radius: nest_radius,
+            })),
+            predator: Mesh2dHandle(meshes.add(Circle { radius: 1.0 })),
             ant_antenna: Mesh2dHandle(meshes.add(Circle {
                 radius: ANT_ANTENNA_RADIUS,
             })),
             ant_segment: Mesh2dHandle(meshes.add(Circle {
                 radius: ANT_SEGMENT_RADIUS,
             })),
-            dirt: Mesh2dHandle(meshes.add(Rectangle::new(WORLD_WIDTH, WORLD_HEIGHT))),
+            dirt: Mesh2dHandle(meshes.add(dirt)),
         }
     }
 }
@@ -35,20 +50,54 @@ impl FromWorld for Meshes {
 pub struct Colors {
     pub ant_worker: Handle<ColorMaterial>,
     pub ant_scout: Handle<ColorMaterial>,
+    pub ant_queen: Handle<ColorMaterial>,
+    pub ant_soldier: Handle<ColorMaterial>,
     pub dirt: Handle<ColorMaterial>,
-    pub food: Handle<ColorMaterial>,
+    pub food_kinds: [Handle<ColorMaterial>; FoodKind::VARIANT_COUNT],
     pub nest: Handle<ColorMaterial>,
+    pub spider: Handle<ColorMaterial>,
+    pub antlion: Handle<ColorMaterial>,
+}
+
+impl Colors {
+    pub fn ant(&self, kind: AntKind) -> Handle<ColorMaterial> {
+        match kind {
+            AntKind::Scout => self.ant_scout.clone(),
+            AntKind::Worker => self.ant_worker.clone(),
+            AntKind::Queen => self.ant_queen.clone(),
+            AntKind::Soldier => self.ant_soldier.clone(),
+        }
+    }
+
+    pub fn predator(&self, kind: PredatorKind) -> Handle<ColorMaterial> {
+        match kind {
+            PredatorKind::Spider => self.spider.clone(),
+            PredatorKind::Antlion => self.antlion.clone(),
+        }
+    }
+
+    pub fn food(&self, kind: FoodKind) -> Handle<ColorMaterial> {
+        self.food_kinds[kind.ordinal() as usize].clone()
+    }
 }
 
 impl FromWorld for Colors {
     fn from_world(world: &mut World) -> Self {
+        let food_kinds = world.resource::<SimulationConfig>().food_kinds.clone();
         let mut colors = world.resource_mut::<Assets<ColorMaterial>>();
         Self {
             ant_worker: colors.add(ANT_COLOR),
             ant_scout: colors.add(Color::srgb(0.0, 0.3, 0.0)),
+            ant_queen: colors.add(Color::srgb(0.45, 0.1, 0.45)),
+            ant_soldier: colors.add(Color::srgb(0.4, 0.05, 0.05)),
             dirt: colors.add(DIRT_COLOR),
             nest: colors.add(NEST_COLOR),
-            food: colors.add(FOOD_COLOR),
+            spider: colors.add(Color::srgb(0.15, 0.1, 0.2)),
+            antlion: colors.add(Color::srgb(0.4, 0.3, 0.2)),
+            food_kinds: std::array::from_fn(|i| {
+                let [r, g, b] = food_kinds[FoodKind::VARIANTS[i]].color;
+                colors.add(Color::srgb_u8(r, g, b))
+            }),
         }
     }
 }
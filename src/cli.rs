@@ -0,0 +1,89 @@
+use std::{path::PathBuf, time::Duration};
+
+use bevy::log::Level;
+use clap::{Parser, ValueEnum};
+
+#[derive(Parser)]
+#[command(about = "Ant colony simulation")]
+pub struct Cli {
+    /// Run without a window or render the simulation
+    #[arg(long, value_enum, default_value_t = Mode::Headless)]
+    pub mode: Mode,
+
+    /// Stop once the simulation has run this many ticks in total, including resumed ones
+    #[arg(long)]
+    pub ticks: Option<u64>,
+
+    /// Stop after this many seconds of wall-clock time
+    #[arg(long, value_parser = parse_seconds)]
+    pub duration: Option<Duration>,
+
+    /// Run headless at this multiple of real time instead of as fast as possible
+    #[arg(long, value_parser = parse_positive)]
+    pub speed: Option<f64>,
+
+    /// Log statistics every this many simulation ticks
+    #[arg(long, default_value_t = 600, value_parser = clap::value_parser!(u64).range(1..))]
+    pub stats_interval: u64,
+
+    /// Write time series of colony metrics to this `.csv` or `.jsonl` file
+    #[arg(long)]
+    pub metrics: Option<PathBuf>,
+
+    /// Record metrics every this many simulation ticks
+    #[arg(long, default_value_t = 60, value_parser = clap::value_parser!(u64).range(1..))]
+    pub metrics_interval: u64,
+
+    /// Restore the world from this snapshot instead of setting up a new one
+    #[arg(long)]
+    pub resume: Option<PathBuf>,
+
+    /// Save a snapshot to this path when the run ends; `{tick}` is replaced by the tick count
+    #[arg(long)]
+    pub snapshot: Option<String>,
+
+    /// Also save a snapshot every this many simulation ticks
+    #[arg(long, requires = "snapshot", value_parser = clap::value_parser!(u64).range(1..))]
+    pub snapshot_interval: Option<u64>,
+
+    /// TOML file of simulation parameters, layered over the defaults
+    #[arg(long)]
+    pub config: Option<PathBuf>,
+
+    /// PNG or text map of walls, nests and food to set the world up from, layered over `--config`
+    #[arg(long, conflicts_with = "resume")]
+    pub scenario: Option<PathBuf>,
+
+    /// Override a simulation parameter, e.g. `--set ant_max_carry=3.0`; may be repeated
+    #[arg(long = "set", value_name = "KEY=VALUE")]
+    pub overrides: Vec<String>,
+
+    /// Seed for the simulation's random number generator, overriding the config; at most
+    /// 9223372036854775807 so it fits in a TOML integer
+    #[arg(long, value_parser = clap::value_parser!(u64).range(..=i64::MAX as u64))]
+    pub seed: Option<u64>,
+
+    /// Minimum level of log messages to print
+    #[arg(long, default_value_t = Level::INFO)]
+    pub log_level: Level,
+}
+
+#[derive(ValueEnum, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Headless,
+    Rendered,
+}
+
+fn parse_positive(value: &str) -> Result<f64, String> {
+    let number: f64 = value.parse().map_err(|err| format!("{err}"))?;
+    if number > 0.0 && number.is_finite() {
+        Ok(number)
+    } else {
+        Err("must be a positive number".to_string())
+    }
+}
+
+fn parse_seconds(value: &str) -> Result<Duration, String> {
+    let seconds: f64 = value.parse().map_err(|err| format!("{err}"))?;
+    Duration::try_from_secs_f64(seconds).map_err(|err| format!("{err}"))
+}
@@ -0,0 +1,351 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ant::{raise_alarm, Ant, AntDied, DeathCause},
+    config::SimulationConfig,
+    food::{spawn_food, Corpse, FoodKind},
+    nest::Colony,
+    predator::{Predator, PredatorKind},
+    queen::Flight,
+    spatial::SpatialIndex,
+    track::Tracks,
+};
+
+#[derive(Component, Clone, Serialize, Deserialize)]
+pub struct Health(f32);
+
+impl Health {
+    pub fn new(amount: f32) -> Self {
+        Self(amount)
+    }
+
+    pub fn amount(&self) -> f32 {
+        self.0
+    }
+}
+
+/// Damage dealt per second to an enemy in reach.
+#[derive(Component, Clone, Copy, Serialize, Deserialize)]
+pub struct Attack(pub f32);
+
+/// Share of incoming damage shrugged off.
+#[derive(Component, Clone, Copy, Serialize, Deserialize)]
+pub struct Armour(pub f32);
+
+type FighterData = (
+    Entity,
+    &'static Transform,
+    &'static Colony,
+    &'static Attack,
+    &'static Armour,
+    &'static mut Health,
+);
+
+type QuarryData = (
+    Entity,
+    &'static Transform,
+    &'static PredatorKind,
+    &'static Armour,
+    &'static mut Health,
+);
+
+/// Every ant strikes the nearest enemy in reach, or failing that the nearest predator, all at
+/// once so the outcome doesn't depend on the order ants are visited in. Each target takes the
+/// sum of its blows in one go; ants that survive raise the alarm once, and the killed, ants and
+/// predators alike, are removed at once and leave a corpse of protein.
+pub fn fight(
+    mut commands: Commands,
+    simulation_config: Res<SimulationConfig>,
+    ant_index: Res<SpatialIndex<Ant>>,
+    mut ants: Query<FighterData, (With<Ant>, Without<Flight>)>,
+    mut predators: Query<QuarryData, (With<Predator>, Without<Ant>)>,
+    mut tracks: Query<&mut Tracks>,
+    mut deaths: EventWriter<AntDied>,
+) {
+    let mut tracks = tracks.single_mut();
+    let combat_config = &simulation_config.combat;
+    let delta_time = simulation_config.fixed_delta_time();
+
+    let mut blows = vec![];
+    let mut predator_blows = vec![];
+    for (_, transform, colony, attack, _, _) in ants.iter() {
+        if attack.0 <= 0.0 {
+            continue;
+        }
+        let position = transform.translation.xy();
+        let target = ant_index.nearest_within(position, combat_config.engage_distance, |other| {
+            ants.get(other)
+                .is_ok_and(|(_, _, other_colony, ..)| other_colony != colony)
+        });
+        if let Some(target) = target {
+            let (_, target_transform, _, _, armour, _) = ants.get(target).unwrap();
+            blows.push((
+                target,
+                target_transform.translation.xy(),
+                attack.0 * (1.0 - armour.0) * delta_time,
+            ));
+            continue;
+        }
+        // Predators are reached at the edge of their body, and ties go the same way every run.
+        let predator = predators
+            .iter()
+            .map(|(entity, predator_transform, kind, armour, _)| {
+                let predator_position = predator_transform.translation.xy();
+                let gap = predator_position.distance(position)
+                    - simulation_config.predators[*kind].capture_radius;
+                (gap, predator_position, entity, armour)
+            })
+            .filter(|(gap, ..)| *gap < combat_config.engage_distance)
+            .min_by(|(a, a_position, ..), (b, b_position, ..)| {
+                a.total_cmp(b)
+                    .then(a_position.x.total_cmp(&b_position.x))
+                    .then(a_position.y.total_cmp(&b_position.y))
+            });
+        if let Some((_, target_position, target, armour)) = predator {
+            predator_blows.push((
+                target,
+                target_position,
+                attack.0 * (1.0 - armour.0) * delta_time,
+            ));
+        }
+    }
+
+    for (target, damage) in total_damage(predator_blows) {
+        let (_, transform, _, _, mut health) = predators.get_mut(target).unwrap();
+        health.0 -= damage;
+        if health.0 <= 0.0 {
+            let position = transform.translation;
+            commands.entity(target).despawn_recursive();
+            leave_corpse(&mut commands, &simulation_config, position);
+        }
+    }
+
+    for (target, damage) in total_damage(blows) {
+        let (_, transform, colony, _, _, mut health) = ants.get_mut(target).unwrap();
+        health.0 -= damage;
+        if health.0 > 0.0 {
+            raise_alarm(
+                &mut tracks,
+                &simulation_config,
+                *colony,
+                transform.translation.xy(),
+            );
+            continue;
+        }
+        deaths.send(AntDied {
+            entity: target,
+            colony: *colony,
+            cause: DeathCause::Combat,
+        });
+        commands.entity(target).despawn_recursive();
+        leave_corpse(&mut commands, &simulation_config, transform.translation);
+    }
+}
+
+/// Adds up the blows each target takes, summed and returned in order of where the targets stand
+/// so neither the totals nor the order they are dealt in depend on who struck first.
+fn total_damage(mut blows: Vec<(Entity, Vec2, f32)>) -> Vec<(Entity, f32)> {
+    blows.sort_by(|(a, a_position, a_damage), (b, b_position, b_damage)| {
+        a_position
+            .x
+            .total_cmp(&b_position.x)
+            .then(a_position.y.total_cmp(&b_position.y))
+            .then(a.cmp(b))
+            .then(a_damage.total_cmp(b_damage))
+    });
+    let mut totals: Vec<(Entity, f32)> = vec![];
+    for (target, _, damage) in blows {
+        match totals.last_mut() {
+            Some((last, total)) if *last == target => *total += damage,
+            _ => totals.push((target, damage)),
+        }
+    }
+    totals
+}
+
+fn leave_corpse(commands: &mut Commands, simulation_config: &SimulationConfig, position: Vec3) {
+    let corpse_food = simulation_config.combat.corpse_food;
+    if corpse_food > 0.0 {
+        let corpse = spawn_food(
+            commands,
+            position.x,
+            position.y,
+            FoodKind::Protein,
+            corpse_food,
+        );
+        commands.entity(corpse).insert(Corpse);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+    use crate::{
+        ant::{spawn_ant, AntKind},
+        food::Food,
+        predator::spawn_predator,
+        spatial::update_spatial_index,
+        track::Pheromone,
+    };
+
+    /// A fighter to place: where it stands, its colony, attack, armour and health.
+    type Fighter = (Vec2, u32, f32, f32, f32);
+
+    /// A world stepping one second per run with `fighters` spawned in the order given, and a
+    /// schedule that fights once per run.
+    fn combat_world(fighters: &[Fighter]) -> (World, Schedule) {
+        let mut world = World::new();
+        let simulation_config = SimulationConfig {
+            ticks_per_second: 1.0,
+            ..default()
+        };
+        world.insert_resource(SpatialIndex::<Ant>::from_config(&simulation_config));
+        world.spawn(Tracks::new(Vec2::new(400.0, 300.0), 4.0, 2));
+        world.insert_resource(simulation_config);
+        world.init_resource::<Events<AntDied>>();
+        for &(position, colony, attack, armour, health) in fighters {
+            let ant = world.run_system_once(
+                move |mut commands: Commands, simulation_config: Res<SimulationConfig>| {
+                    spawn_ant(
+                        &mut commands,
+                        &simulation_config,
+                        position.x,
+                        position.y,
+                        0.0,
+                        AntKind::Worker,
+                        Colony(colony),
+                    )
+                },
+            );
+            world
+                .entity_mut(ant)
+                .insert((Attack(attack), Armour(armour), Health(health)));
+        }
+        let mut schedule = Schedule::default();
+        schedule.add_systems((update_spatial_index::<Ant>, fight).chain());
+        (world, schedule)
+    }
+
+    /// The health of each ant still standing, in order of position.
+    fn healths(world: &mut World) -> Vec<(Vec2, f32)> {
+        let mut healths: Vec<_> = world
+            .query_filtered::<(&Transform, &Health), With<Ant>>()
+            .iter(world)
+            .map(|(transform, health)| (transform.translation.xy(), health.0))
+            .collect();
+        healths.sort_by(|(a, _), (b, _)| a.x.total_cmp(&b.x).then(a.y.total_cmp(&b.y)));
+        healths
+    }
+
+    fn corpses(world: &mut World) -> Vec<(Vec2, f32)> {
+        world
+            .query_filtered::<(&Transform, &Food), With<Corpse>>()
+            .iter(world)
+            .map(|(transform, food)| (transform.translation.xy(), food.amount()))
+            .collect()
+    }
+
+    fn alarm(world: &mut World, colony: u32) -> f32 {
+        world.query::<&Tracks>().single(world).total(Colony(colony))[Pheromone::Alarm]
+    }
+
+    #[test]
+    fn armour_takes_its_share_off_every_blow() {
+        let (mut world, mut schedule) = combat_world(&[
+            (Vec2::new(2.0, 2.0), 0, 2.0, 0.0, 10.0),
+            (Vec2::new(5.0, 2.0), 1, 4.0, 0.25, 10.0),
+        ]);
+        schedule.run(&mut world);
+        assert_eq!(
+            healths(&mut world),
+            [(Vec2::new(2.0, 2.0), 6.0), (Vec2::new(5.0, 2.0), 8.5)]
+        );
+        // Both were hit and lived, so both called for help.
+        assert!(alarm(&mut world, 0) > 0.0);
+        assert!(alarm(&mut world, 1) > 0.0);
+    }
+
+    #[test]
+    fn killed_ants_leave_a_corpse() {
+        let (mut world, mut schedule) = combat_world(&[
+            (Vec2::ZERO, 0, 10.0, 0.0, 10.0),
+            (Vec2::new(3.0, 0.0), 1, 0.0, 0.0, 1.0),
+        ]);
+        schedule.run(&mut world);
+        assert_eq!(healths(&mut world), [(Vec2::ZERO, 10.0)]);
+        assert_eq!(corpses(&mut world), [(Vec2::new(3.0, 0.0), 5.0)]);
+        let deaths: Vec<_> = world
+            .resource_mut::<Events<AntDied>>()
+            .drain()
+            .map(|death| (death.colony, death.cause.name()))
+            .collect();
+        assert!(deaths == [(Colony(1), DeathCause::Combat.name())]);
+        // The dead don't raise the alarm.
+        assert_eq!(alarm(&mut world, 1), 0.0);
+    }
+
+    #[test]
+    fn killed_predators_leave_a_corpse() {
+        let (mut world, mut schedule) = combat_world(&[(Vec2::ZERO, 0, 100.0, 0.0, 10.0)]);
+        let reach = world.resource::<SimulationConfig>().predators[PredatorKind::Spider]
+            .capture_radius
+            + 1.0;
+        world.run_system_once(
+            move |mut commands: Commands, simulation_config: Res<SimulationConfig>| {
+                spawn_predator(
+                    &mut commands,
+                    &simulation_config,
+                    reach,
+                    0.0,
+                    0.0,
+                    PredatorKind::Spider,
+                );
+            },
+        );
+        schedule.run(&mut world);
+        assert_eq!(
+            world
+                .query_filtered::<(), With<Predator>>()
+                .iter(&world)
+                .count(),
+            0
+        );
+        assert_eq!(corpses(&mut world), [(Vec2::new(reach, 0.0), 5.0)]);
+        assert_eq!(healths(&mut world), [(Vec2::ZERO, 10.0)]);
+    }
+
+    #[test]
+    fn blows_land_the_same_whatever_order_ants_are_visited_in() {
+        // Three attackers around one ant that doesn't strike back.
+        let mut fighters = vec![
+            (Vec2::new(2.0, 2.0), 1, 0.0, 0.0, 10.0),
+            (Vec2::new(5.0, 2.0), 0, 0.7, 0.0, 10.0),
+            (Vec2::new(-1.0, 2.0), 0, 1.3, 0.0, 10.0),
+            (Vec2::new(2.0, 5.0), 0, 0.1, 0.0, 10.0),
+        ];
+        let (mut forward, mut forward_schedule) = combat_world(&fighters);
+        fighters.reverse();
+        let (mut backward, mut backward_schedule) = combat_world(&fighters);
+        forward_schedule.run(&mut forward);
+        backward_schedule.run(&mut backward);
+
+        let healths_forward = healths(&mut forward);
+        assert_eq!(healths_forward, healths(&mut backward));
+        assert!((healths_forward[1].1 - 7.9).abs() < 1e-5);
+        // The alarm is raised once for all three blows.
+        let mut once = Tracks::new(Vec2::new(400.0, 300.0), 4.0, 2);
+        raise_alarm(
+            &mut once,
+            forward.resource::<SimulationConfig>(),
+            Colony(1),
+            Vec2::new(2.0, 2.0),
+        );
+        let raised = once.total(Colony(1))[Pheromone::Alarm];
+        assert!(raised > 0.0);
+        assert_eq!(alarm(&mut forward, 1), raised);
+        assert_eq!(alarm(&mut backward, 1), raised);
+    }
+}
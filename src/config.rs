@@ -1,23 +1,27 @@
+use std::{collections::BTreeMap, fmt, fs, path::Path};
+
 use bevy::prelude::*;
 use enum_ordinalize::Ordinalize;
 use rand::prelude::*;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use toml::Spanned;
 
-use crate::ant::AntKind;
+use crate::{
+    ant::AntKind,
+    food::{FoodKind, FoodLayout, FoodPile, FoodPolicy},
+    obstacle::Obstacle,
+    predator::PredatorKind,
+    track::{Boundary, Pheromone},
+};
 
 pub const CLEAR_COLOR: Color = Color::srgb(0.0, 0.0, 0.0);
 pub const DIRT_COLOR: Color = Color::srgb(155.0 / 255.0, 118.0 / 255.0, 83.0 / 255.0);
 pub const ANT_COLOR: Color = Color::srgb(0.0, 0.0, 0.0);
 pub const NEST_COLOR: Color = Color::srgb(120.0 / 255.0, 82.0 / 255.0, 30.0 / 255.0);
-pub const FOOD_COLOR: Color = Color::srgb(126.0 / 255.0, 196.0 / 255.0, 51.0 / 255.0);
-
-pub const WORLD_WIDTH: f32 = 1920.0;
-pub const WORLD_HEIGHT: f32 = 1080.0;
+pub const OBSTACLE_PIXEL: [u8; 4] = [90, 90, 90, 255];
 
 pub const ANT_ANTENNA_RADIUS: f32 = 1.0;
 pub const ANT_SEGMENT_RADIUS: f32 = 2.0;
-pub const ANT_SPEED: f32 = 10.0;
-pub const ANT_ROTATION_SPEED: f32 = 2.0 * std::f32::consts::PI;
-pub const ANT_ENERGY_LOSS_RATE: f32 = 0.006;
 pub const ANT_MAX_ENERGY: f32 = 1.0;
 
 pub const LAYER_DIRT: f32 = 0.0;
@@ -25,65 +29,882 @@ pub const LAYER_TRACK: f32 = 1.0;
 pub const LAYER_NEST: f32 = 2.0;
 pub const LAYER_FOOD: f32 = 3.0;
 pub const LAYER_ANT: f32 = 4.0;
+pub const LAYER_PREDATOR: f32 = 5.0;
 
 pub const TRACK_RADIUS: f32 = 2.0;
-pub const TRACK_RESOLUTION: f32 = 4.0;
-
-pub const NEST_RADIUS: f32 = 10.0;
 
-pub const TICKS_PER_SECOND: f64 = 60.0;
-pub const FIXED_DELTA_TIME: f32 = 1.0 / TICKS_PER_SECOND as f32;
-pub const TICK_RATE_MULTIPLIER: f64 = 4.0;
-
-#[derive(Resource)]
+#[derive(Resource, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct SimulationConfig {
+    pub seed: Option<u64>,
+    pub world_width: f32,
+    pub world_height: f32,
+    pub ticks_per_second: f64,
+    pub tick_rate_multiplier: f64,
+    pub ant_speed: f32,
+    pub ant_rotation_speed: f32,
+    pub ant_energy_loss_rate: f32,
     pub ant_track_concentration: f32,
     pub ant_sense_distance: f32,
     pub ant_sense_radius: f32,
     pub ant_max_carry: f32,
+    pub nest_radius: f32,
     pub nest_track_concentration: f32,
-    pub track_concentration_factor: f32,
-    pub track_diffusion_factor: f32,
+    /// Where each colony's nest starts, one colony per entry.
+    pub nests: Vec<[f32; 2]>,
+    /// Ants spawned around each nest when the world is set up.
+    pub initial_ants: u32,
+    /// Food piles spawned at random places when the world is set up, besides `initial_food`.
+    pub random_food: u32,
+    pub track_resolution: f32,
+    pub track_boundary: Boundary,
+    /// Size of the grid cells food and nests are indexed in for proximity queries.
+    pub spatial_cell_size: f32,
+    pub food: FoodConfig,
+    pub brood: BroodConfig,
+    pub queen: QueenConfig,
+    pub tasks: TaskConfig,
+    pub combat: CombatConfig,
+    pub alarm: AlarmConfig,
+    pub trophallaxis: TrophallaxisConfig,
+    pub crowding: CrowdingConfig,
+    /// How each kind of predator hunts and how many roam the world; there are none by default.
+    pub predators: PredatorKinds,
+    /// How often each kind of ant ecloses. Queens leave on a nuptial flight as soon as they
+    /// eclose, so a `queen` weight above 0 lets colonies spread.
     pub ant_kind_gen_config: AntKindGenConfig,
+    pub ant_kinds: AntKinds,
+    pub pheromones: PheromoneChannels,
+    pub food_kinds: FoodKinds,
+    pub obstacles: Vec<Obstacle>,
+    pub initial_food: Vec<FoodPile>,
+    /// Rules turning ants of one kind into another, tried in order every tick.
+    pub kind_changes: Vec<KindChange>,
 }
 
 impl Default for SimulationConfig {
     fn default() -> Self {
         Self {
+            seed: None,
+            world_width: 1920.0,
+            world_height: 1080.0,
+            ticks_per_second: 60.0,
+            tick_rate_multiplier: 4.0,
+            ant_speed: 10.0,
+            ant_rotation_speed: 2.0 * std::f32::consts::PI,
+            ant_energy_loss_rate: 0.006,
             ant_track_concentration: 0.1,
             ant_sense_distance: 12.0,
             ant_sense_radius: 8.0,
             ant_max_carry: 5.0,
+            nest_radius: 10.0,
             nest_track_concentration: 0.1,
-            track_concentration_factor: 0.99,
-            track_diffusion_factor: 0.001,
+            nests: vec![[0.0, 0.0]],
+            initial_ants: 100,
+            random_food: 25,
+            track_resolution: 4.0,
+            track_boundary: Boundary::default(),
+            spatial_cell_size: 32.0,
+            food: FoodConfig::default(),
+            brood: BroodConfig::default(),
+            queen: QueenConfig::default(),
+            tasks: TaskConfig::default(),
+            combat: CombatConfig::default(),
+            alarm: AlarmConfig::default(),
+            trophallaxis: TrophallaxisConfig::default(),
+            crowding: CrowdingConfig::default(),
+            predators: PredatorKinds::default(),
             ant_kind_gen_config: AntKindGenConfig::new([
                 (AntKind::Worker, 1.0),
                 (AntKind::Scout, 1.0),
-            ]),
+                (AntKind::Queen, 0.0),
+                (AntKind::Soldier, 0.1),
+            ])
+            .expect("default ant kind weights are valid"),
+            ant_kinds: AntKinds::default(),
+            pheromones: PheromoneChannels::default(),
+            food_kinds: FoodKinds::default(),
+            obstacles: vec![],
+            initial_food: vec![],
+            kind_changes: vec![],
+        }
+    }
+}
+
+impl SimulationConfig {
+    pub fn fixed_delta_time(&self) -> f32 {
+        (1.0 / self.ticks_per_second) as f32
+    }
+
+    /// Builds a config from the defaults overridden by each layer in turn, so later layers win.
+    pub fn from_layers(layers: &[ConfigLayer]) -> Result<Self, Vec<ConfigError>> {
+        let mut values = BTreeMap::new();
+        let mut errors = vec![];
+        for layer in layers {
+            // Only keys are spanned: spanned values can't hold nested tables such as
+            // `[pheromones.food]`.
+            match toml::from_str::<BTreeMap<Spanned<String>, toml::Value>>(&layer.text) {
+                Ok(table) => {
+                    for (key, value) in table {
+                        let offset = key.span().start;
+                        let key = key.into_inner();
+                        // Tables are merged key by key so a layer only overrides what it sets;
+                        // errors point at the last layer that touched the key.
+                        let value = match values.remove(&key) {
+                            Some((_, _, mut merged)) => {
+                                merge(&mut merged, value);
+                                merged
+                            }
+                            None => value,
+                        };
+                        values.insert(key, (layer, offset, value));
+                    }
+                }
+                Err(err) => errors.push(ConfigError {
+                    source: layer.name.clone(),
+                    line: err.span().map(|span| layer.line_of(span.start)),
+                    key: None,
+                    message: describe(&err),
+                }),
+            }
+        }
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        // Deserialize each key on its own first so errors can point at the line that set it.
+        for (key, (layer, offset, value)) in values.iter() {
+            let table = toml::Table::from_iter([(key.clone(), value.clone())]);
+            if let Err(err) = Self::deserialize(toml::Value::Table(table)) {
+                errors.push(layer.error_at(key, *offset, &describe(&err)));
+            }
+        }
+        if !errors.is_empty() {
+            errors.sort_by_key(|error| error.line);
+            return Err(errors);
+        }
+
+        let table = values
+            .iter()
+            .map(|(key, (_, _, value))| (key.clone(), value.clone()))
+            .collect();
+        let config = Self::deserialize(toml::Value::Table(table)).map_err(|err| {
+            vec![ConfigError {
+                source: "config".to_string(),
+                line: None,
+                key: None,
+                message: describe(&err),
+            }]
+        })?;
+
+        let mut errors: Vec<_> = config
+            .validate()
+            .into_iter()
+            .map(|(key, message)| match values.get(key) {
+                Some((layer, offset, _)) => layer.error_at(key, *offset, &message),
+                None => ConfigError {
+                    source: "defaults".to_string(),
+                    line: None,
+                    key: Some(key.to_string()),
+                    message,
+                },
+            })
+            .collect();
+        if !errors.is_empty() {
+            errors.sort_by_key(|error| error.line);
+            return Err(errors);
+        }
+
+        Ok(config)
+    }
+
+    fn validate(&self) -> Vec<(&'static str, String)> {
+        let mut errors = vec![];
+        let mut check = |key: &'static str, valid: bool, requirement: &str| {
+            if !valid {
+                errors.push((key, format!("must be {requirement}")));
+            }
+        };
+
+        check("world_width", self.world_width > 0.0, "greater than 0");
+        check("world_height", self.world_height > 0.0, "greater than 0");
+        check(
+            "ticks_per_second",
+            self.ticks_per_second > 0.0,
+            "greater than 0",
+        );
+        check(
+            "tick_rate_multiplier",
+            self.tick_rate_multiplier > 0.0,
+            "greater than 0",
+        );
+        check("ant_speed", self.ant_speed >= 0.0, "at least 0");
+        check(
+            "ant_rotation_speed",
+            self.ant_rotation_speed >= 0.0,
+            "at least 0",
+        );
+        check(
+            "ant_energy_loss_rate",
+            self.ant_energy_loss_rate >= 0.0,
+            "at least 0",
+        );
+        check(
+            "ant_track_concentration",
+            self.ant_track_concentration >= 0.0,
+            "at least 0",
+        );
+        check(
+            "ant_sense_distance",
+            self.ant_sense_distance >= 0.0,
+            "at least 0",
+        );
+        check(
+            "ant_sense_radius",
+            self.ant_sense_radius > 0.0,
+            "greater than 0",
+        );
+        check("ant_max_carry", self.ant_max_carry > 0.0, "greater than 0");
+        check("nest_radius", self.nest_radius > 0.0, "greater than 0");
+        check(
+            "nest_track_concentration",
+            self.nest_track_concentration >= 0.0,
+            "at least 0",
+        );
+        check(
+            "nests",
+            !self.nests.is_empty()
+                && self.nests.iter().all(|[x, y]| {
+                    x.abs() < self.world_width / 2.0 && y.abs() < self.world_height / 2.0
+                }),
+            "a non-empty list of positions inside the world",
+        );
+        check(
+            "obstacles",
+            self.obstacles.iter().all(Obstacle::is_valid),
+            "a list of shapes with sizes greater than 0",
+        );
+        check(
+            "obstacles",
+            !self.nests.iter().any(|nest| {
+                self.obstacles
+                    .iter()
+                    .any(|obstacle| obstacle.contains(Vec2::from(*nest)))
+            }),
+            "clear of every nest",
+        );
+        let food = &self.food;
+        check(
+            "food",
+            food.amount[0] > 0.0
+                && food.amount[0] <= food.amount[1]
+                && (food.layout != FoodLayout::Clustered
+                    || (food.patches > 0 && food.patch_radius >= 0.0))
+                && food.arrival_rate >= 0.0
+                && food.regrowth_rate >= 0.0
+                && food.total_supply >= 0.0,
+            "a table with 0 < amount[0] <= amount[1], at least one patch when clustered and no \
+             negative rates or supply",
+        );
+        let brood = &self.brood;
+        check(
+            "brood",
+            brood.egg_interval > 0.0
+                && brood.egg_cost >= 0.0
+                && brood.egg_duration >= 0.0
+                && brood.larva_food >= 0.0
+                && brood.larva_feed_rate > 0.0
+                && brood.larva_starvation_time >= 0.0
+                && brood.pupa_duration >= 0.0,
+            "a table with positive egg_interval and larva_feed_rate and no negative durations or \
+             costs",
+        );
+        let queen = &self.queen;
+        check(
+            "queen",
+            queen.energy_loss_rate >= 0.0
+                && queen.flight_speed > 0.0
+                && queen.min_nest_distance >= 0.0
+                && queen.min_nest_distance <= queen.flight_distance,
+            "a table with a positive flight_speed, no negative rates and \
+             0 <= min_nest_distance <= flight_distance",
+        );
+        check(
+            "food_kinds",
+            FoodKind::VARIANTS
+                .iter()
+                .any(|&kind| self.food_kinds[kind].abundance > 0.0),
+            "a table where at least one kind has an abundance above 0",
+        );
+        check(
+            "initial_food",
+            self.initial_food.iter().all(|pile| {
+                pile.amount > 0.0
+                    && pile.position[0].abs() < self.world_width / 2.0
+                    && pile.position[1].abs() < self.world_height / 2.0
+            }),
+            "a list of piles with amounts greater than 0 inside the world",
+        );
+        let tasks = &self.tasks;
+        check(
+            "tasks",
+            tasks.switch_rate >= 0.0
+                && tasks.quit_rate >= 0.0
+                && tasks.food_per_ant > 0.0
+                && tasks.encounter_weight >= 0.0
+                && tasks.trail_half > 0.0
+                && tasks.brood_per_nurse > 0.0
+                && tasks.nursing_speedup >= 0.0
+                && (0.0..=1.0).contains(&tasks.rest_energy_factor),
+            "a table with positive food_per_ant, trail_half and brood_per_nurse, no negative \
+             rates or weights and rest_energy_factor between 0 and 1",
+        );
+        let combat = &self.combat;
+        check(
+            "combat",
+            combat.engage_distance >= 0.0
+                && combat.corpse_food >= 0.0
+                && combat.patrol_radius >= 0.0,
+            "a table with no negative distances or amounts",
+        );
+        let alarm = &self.alarm;
+        check(
+            "alarm",
+            alarm.concentration >= 0.0
+                && (0.0..=1.0).contains(&alarm.hunger)
+                && alarm.attraction >= 0.0
+                && alarm.repulsion >= 0.0,
+            "a table with no negative amounts and hunger between 0 and 1",
+        );
+        let trophallaxis = &self.trophallaxis;
+        check(
+            "trophallaxis",
+            trophallaxis.rate >= 0.0
+                && trophallaxis.reach >= 0.0
+                && trophallaxis.hunger >= 0.0
+                && trophallaxis.hunger <= trophallaxis.reserve
+                && trophallaxis.reserve <= 1.0,
+            "a table with no negative rate or reach and 0 <= hunger <= reserve <= 1",
+        );
+        let crowding = &self.crowding;
+        check(
+            "crowding",
+            crowding.body_radius >= 0.0
+                && crowding.radius >= 0.0
+                && crowding.slowdown >= 0.0
+                && (0.0..=1.0).contains(&crowding.stiffness),
+            "a table with no negative sizes or slowdown and stiffness between 0 and 1",
+        );
+        check(
+            "kind_changes",
+            self.kind_changes.iter().all(|change| {
+                change.from != change.to
+                    && change.from.takes_tasks()
+                    && change.to.takes_tasks()
+                    && change.min_age >= 0.0
+                    && change.max_share > 0.0
+                    && change.max_share <= 1.0
+            }),
+            "a list of changes between two different kinds other than queens and soldiers, with \
+             min_age at least 0 and 0 < max_share <= 1",
+        );
+        check(
+            "track_resolution",
+            self.track_resolution > 0.0
+                && self.track_resolution <= self.world_width.min(self.world_height),
+            "greater than 0 and at most the world size",
+        );
+        // Every cell costs memory, so keep the index to about a million of them.
+        check(
+            "spatial_cell_size",
+            self.spatial_cell_size > 0.0
+                && self.spatial_cell_size >= self.world_width.max(self.world_height) / 1024.0,
+            "greater than 0 and at least 1/1024 of the world's larger side",
+        );
+
+        errors
+    }
+}
+
+/// How one kind of food nourishes, burdens and lasts, and how it is drawn.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FoodKindConfig {
+    /// Satiation gained from eating one unit.
+    pub energy: f32,
+    /// How much one unit counts against `ant_max_carry`.
+    pub weight: f32,
+    /// Seconds an ant spends at a pile before it can carry any away.
+    pub pickup_time: f32,
+    /// Fraction of the food stored in a nest lost each second.
+    pub spoilage_rate: f32,
+    /// Relative chance of a random pile being this kind.
+    pub abundance: f32,
+    pub color: [u8; 3],
+}
+
+impl FoodKindConfig {
+    fn check(&self, kind: FoodKind) -> Result<(), String> {
+        let name = kind.name();
+        if self.energy.is_nan() || self.energy <= 0.0 {
+            return Err(format!("energy of {name} must be greater than 0"));
+        }
+        if self.weight.is_nan() || self.weight <= 0.0 {
+            return Err(format!("weight of {name} must be greater than 0"));
+        }
+        if self.pickup_time.is_nan() || self.pickup_time < 0.0 {
+            return Err(format!("pickup_time of {name} must be at least 0"));
+        }
+        if !(0.0..=1.0).contains(&self.spoilage_rate) {
+            return Err(format!("spoilage_rate of {name} must be between 0 and 1"));
+        }
+        if self.abundance.is_nan() || self.abundance < 0.0 {
+            return Err(format!("abundance of {name} must be at least 0"));
+        }
+        Ok(())
+    }
+}
+
+/// The settings of every [`FoodKind`]; kinds or fields left out of a config keep their
+/// defaults.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(
+    try_from = "BTreeMap<FoodKind, toml::Table>",
+    into = "BTreeMap<FoodKind, FoodKindConfig>"
+)]
+pub struct FoodKinds([FoodKindConfig; FoodKind::VARIANT_COUNT]);
+
+impl FoodKinds {
+    /// A random kind, weighted by abundance.
+    pub fn gen_kind(&self, rng: &mut impl Rng) -> FoodKind {
+        *FoodKind::VARIANTS
+            .choose_weighted(rng, |kind| self[*kind].abundance)
+            .unwrap()
+    }
+}
+
+impl Default for FoodKinds {
+    fn default() -> Self {
+        Self(std::array::from_fn(|i| match FoodKind::VARIANTS[i] {
+            FoodKind::Sugar => FoodKindConfig {
+                energy: 1.0,
+                weight: 1.0,
+                pickup_time: 0.0,
+                spoilage_rate: 0.0,
+                abundance: 2.0,
+                color: [126, 196, 51],
+            },
+            FoodKind::Protein => FoodKindConfig {
+                energy: 2.0,
+                weight: 2.0,
+                pickup_time: 1.0,
+                spoilage_rate: 0.002,
+                abundance: 1.0,
+                color: [196, 90, 70],
+            },
+            FoodKind::Seeds => FoodKindConfig {
+                energy: 0.5,
+                weight: 0.5,
+                pickup_time: 2.0,
+                spoilage_rate: 0.0,
+                abundance: 1.0,
+                color: [220, 190, 110],
+            },
+        }))
+    }
+}
+
+impl std::ops::Index<FoodKind> for FoodKinds {
+    type Output = FoodKindConfig;
+
+    fn index(&self, kind: FoodKind) -> &Self::Output {
+        &self.0[kind.ordinal() as usize]
+    }
+}
+
+impl TryFrom<BTreeMap<FoodKind, toml::Table>> for FoodKinds {
+    type Error = String;
+
+    fn try_from(kinds: BTreeMap<FoodKind, toml::Table>) -> Result<Self, Self::Error> {
+        let mut result = Self::default();
+        overlay_kinds(&mut result.0, kinds, FoodKindConfig::check)?;
+        Ok(result)
+    }
+}
+
+impl From<FoodKinds> for BTreeMap<FoodKind, FoodKindConfig> {
+    fn from(kinds: FoodKinds) -> Self {
+        FoodKind::VARIANTS.iter().copied().zip(kinds.0).collect()
+    }
+}
+
+/// How eggs become adult ants. Durations are in seconds and food is in energy.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BroodConfig {
+    /// Time between two eggs from a well-fed queen; a hungry one lays more slowly.
+    pub egg_interval: f32,
+    /// Nest food the queen uses up to lay an egg.
+    pub egg_cost: f32,
+    pub egg_duration: f32,
+    /// Food a larva must eat before it pupates.
+    pub larva_food: f32,
+    /// Most food a larva eats per second.
+    pub larva_feed_rate: f32,
+    /// How long a larva survives without enough food.
+    pub larva_starvation_time: f32,
+    pub pupa_duration: f32,
+}
+
+impl Default for BroodConfig {
+    fn default() -> Self {
+        Self {
+            egg_interval: 30.0,
+            egg_cost: 0.2,
+            egg_duration: 20.0,
+            larva_food: 0.8,
+            larva_feed_rate: 0.02,
+            larva_starvation_time: 60.0,
+            pupa_duration: 30.0,
+        }
+    }
+}
+
+/// How queens live in their nests and how new ones fly off to found colonies.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct QueenConfig {
+    /// Energy a queen uses per second, in place of `ant_energy_loss_rate`.
+    pub energy_loss_rate: f32,
+    pub flight_speed: f32,
+    /// Farthest a young queen flies from the nest she eclosed in.
+    pub flight_distance: f32,
+    /// How close to another nest a queen can land and still found a colony.
+    pub min_nest_distance: f32,
+}
+
+impl Default for QueenConfig {
+    fn default() -> Self {
+        Self {
+            energy_loss_rate: 0.002,
+            flight_speed: 40.0,
+            flight_distance: 500.0,
+            min_nest_distance: 150.0,
+        }
+    }
+}
+
+/// How ants switch between tasks. Each stimulus is 0 when nothing needs doing and grows with
+/// the need; an ant takes up a task at a rate that rises steeply once the stimulus passes its
+/// threshold for it.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TaskConfig {
+    /// How often per second a resting ant takes up a task whose stimulus is far above its
+    /// threshold.
+    pub switch_rate: f32,
+    /// Chance per second that a busy ant goes back to resting.
+    pub quit_rate: f32,
+    /// Nest food energy per ant below which the colony wants more foragers.
+    pub food_per_ant: f32,
+    /// How much foragers seen bringing food in, as a share of the colony, add to the stimulus
+    /// to forage.
+    pub encounter_weight: f32,
+    /// Total food pheromone at which the colony's trails count as half strong; weak trails are
+    /// the stimulus to scout.
+    pub trail_half: f32,
+    /// Eggs, larvae and pupae one nurse can tend; brood beyond that is the stimulus for nest
+    /// work.
+    pub brood_per_nurse: f32,
+    /// How much faster eggs and pupae develop when fully tended.
+    pub nursing_speedup: f32,
+    /// Share of `ant_energy_loss_rate` resting ants use.
+    pub rest_energy_factor: f32,
+}
+
+impl Default for TaskConfig {
+    fn default() -> Self {
+        Self {
+            switch_rate: 0.5,
+            quit_rate: 0.01,
+            food_per_ant: 0.5,
+            encounter_weight: 2.0,
+            trail_half: 50.0,
+            brood_per_nurse: 3.0,
+            nursing_speedup: 1.0,
+            rest_energy_factor: 0.5,
+        }
+    }
+}
+
+/// How ants of different colonies fight when they meet.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CombatConfig {
+    /// How close two enemies must be to fight.
+    pub engage_distance: f32,
+    /// Protein left where an ant or predator is killed.
+    pub corpse_food: f32,
+    /// How far from their nest soldiers patrol.
+    pub patrol_radius: f32,
+}
+
+impl Default for CombatConfig {
+    fn default() -> Self {
+        Self {
+            engage_distance: ANT_SEGMENT_RADIUS * 2.0,
+            corpse_food: 5.0,
+            patrol_radius: 120.0,
+        }
+    }
+}
+
+/// When ants raise the alarm and how others respond to it.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AlarmConfig {
+    /// Alarm pheromone laid per second by an ant in trouble.
+    pub concentration: f32,
+    /// Share of full energy below which a starving ant raises the alarm.
+    pub hunger: f32,
+    /// How strongly defending soldiers head for alarm.
+    pub attraction: f32,
+    /// How strongly foraging and scouting ants shy away from alarm.
+    pub repulsion: f32,
+}
+
+impl Default for AlarmConfig {
+    fn default() -> Self {
+        Self {
+            concentration: 0.5,
+            hunger: 0.1,
+            attraction: 20.0,
+            repulsion: 20.0,
+        }
+    }
+}
+
+/// How ants feed each other mouth to mouth.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TrophallaxisConfig {
+    /// Energy per second an ant can pass to a nestmate.
+    pub rate: f32,
+    /// How close two ants must be to share food.
+    pub reach: f32,
+    /// Share of full energy below which an ant is fed by nestmates.
+    pub hunger: f32,
+    /// Share of full energy an ant keeps in its own crop when feeding others.
+    pub reserve: f32,
+}
+
+impl Default for TrophallaxisConfig {
+    fn default() -> Self {
+        Self {
+            rate: 0.2,
+            reach: ANT_SEGMENT_RADIUS * 2.0,
+            hunger: 0.4,
+            reserve: 0.7,
+        }
+    }
+}
+
+/// How ants get in each other's way.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CrowdingConfig {
+    /// Ants closer than twice this overlap and are pushed apart.
+    pub body_radius: f32,
+    /// How far in front an ant minds the ants in its way.
+    pub radius: f32,
+    /// How much each ant in the way slows an ant down.
+    pub slowdown: f32,
+    /// Share of an overlap undone each tick, or 0 to let ants pass through each other.
+    pub stiffness: f32,
+}
+
+impl Default for CrowdingConfig {
+    fn default() -> Self {
+        Self {
+            body_radius: ANT_SEGMENT_RADIUS,
+            radius: ANT_SEGMENT_RADIUS * 4.0,
+            slowdown: 0.5,
+            stiffness: 0.5,
+        }
+    }
+}
+
+/// How random food piles are placed and replenished.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FoodConfig {
+    pub policy: FoodPolicy,
+    pub layout: FoodLayout,
+    /// The range random pile amounts are drawn from.
+    pub amount: [f32; 2],
+    pub patches: u32,
+    pub patch_radius: f32,
+    /// Piles per second arriving under the `poisson` policy.
+    pub arrival_rate: f32,
+    pub max_piles: u32,
+    /// Food per second each pile regrows under the `regrowing` policy.
+    pub regrowth_rate: f32,
+    /// Food handed out in total under the `finite` policy, including the initial random piles.
+    pub total_supply: f32,
+}
+
+impl Default for FoodConfig {
+    fn default() -> Self {
+        Self {
+            policy: FoodPolicy::default(),
+            layout: FoodLayout::default(),
+            amount: [50.0, 250.0],
+            patches: 4,
+            patch_radius: 80.0,
+            arrival_rate: 0.5,
+            max_piles: 50,
+            regrowth_rate: 5.0,
+            total_supply: 10000.0,
         }
     }
 }
 
+/// A named piece of TOML, such as a config file or a `--set` override from the command line.
+pub struct ConfigLayer {
+    pub name: String,
+    pub text: String,
+}
+
+impl ConfigLayer {
+    pub fn from_file(path: &Path) -> Result<Self, ConfigError> {
+        let name = path.display().to_string();
+        match fs::read_to_string(path) {
+            Ok(text) => Ok(Self { name, text }),
+            Err(err) => Err(ConfigError {
+                source: name,
+                line: None,
+                key: None,
+                message: err.to_string(),
+            }),
+        }
+    }
+
+    fn line_of(&self, offset: usize) -> usize {
+        self.text[..offset].matches('\n').count() + 1
+    }
+
+    fn error_at(&self, key: &str, offset: usize, message: &str) -> ConfigError {
+        ConfigError {
+            source: self.name.clone(),
+            line: Some(self.line_of(offset)),
+            key: Some(key.to_string()),
+            message: message.to_string(),
+        }
+    }
+}
+
+/// Merges `value` over `base`: tables key by key, anything else by replacing it.
+fn merge(base: &mut toml::Value, value: toml::Value) {
+    match (base, value) {
+        (toml::Value::Table(base), toml::Value::Table(table)) => {
+            for (key, value) in table {
+                match base.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, value) => *base = value,
+    }
+}
+
+/// Lays per-kind tables such as `[pheromones.food]` over the settings of each kind, so a table
+/// only overrides the fields it sets, then checks every kind it touched.
+fn overlay_kinds<K: Ordinalize + Copy + PartialEq + Ord, T: Serialize + DeserializeOwned>(
+    settings: &mut [T],
+    tables: BTreeMap<K, toml::Table>,
+    check: impl Fn(&T, K) -> Result<(), String>,
+) -> Result<(), String> {
+    for (kind, table) in tables {
+        let i = K::VARIANTS
+            .iter()
+            .position(|variant| *variant == kind)
+            .unwrap();
+        let mut value = toml::Value::try_from(&settings[i]).map_err(|err| err.to_string())?;
+        merge(&mut value, toml::Value::Table(table));
+        let kind_settings = value.try_into().map_err(|err| describe(&err))?;
+        check(&kind_settings, kind)?;
+        settings[i] = kind_settings;
+    }
+    Ok(())
+}
+
+fn describe(err: &toml::de::Error) -> String {
+    let message = err.message().trim();
+    if message.is_empty() {
+        "invalid TOML".to_string()
+    } else {
+        message.replace('\n', ", ")
+    }
+}
+
+#[derive(Debug)]
+pub struct ConfigError {
+    pub source: String,
+    pub line: Option<usize>,
+    pub key: Option<String>,
+    pub message: String,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.source)?;
+        if let Some(line) = self.line {
+            write!(f, ":{line}")?;
+        }
+        if let Some(key) = &self.key {
+            write!(f, ": `{key}`")?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(try_from = "BTreeMap<AntKind, f32>", into = "BTreeMap<AntKind, f32>")]
 pub struct AntKindGenConfig {
     weights: [(AntKind, f32); AntKind::VARIANT_COUNT],
 }
 
 impl AntKindGenConfig {
-    pub fn new(weights: [(AntKind, f32); AntKind::VARIANT_COUNT]) -> Self {
+    pub fn new(weights: [(AntKind, f32); AntKind::VARIANT_COUNT]) -> Result<Self, String> {
         let mut seen = [false; AntKind::VARIANT_COUNT];
-        for (kind, _) in weights.iter() {
-            assert!(
-                !seen[kind.ordinal() as usize],
-                "duplicate ant kind in gen config"
-            );
+        for (kind, weight) in weights.iter() {
+            if seen[kind.ordinal() as usize] {
+                return Err(format!("duplicate weight for {} ants", kind.name()));
+            }
             seen[kind.ordinal() as usize] = true;
+            if weight.is_nan() || *weight < 0.0 {
+                return Err(format!(
+                    "weight for {} ants must be at least 0",
+                    kind.name()
+                ));
+            }
         }
-        assert!(
-            seen.iter().all(|&seen| seen),
-            "missing ant kind in gen config"
-        );
-        Self { weights }
+        if let Some(kind) = AntKind::VARIANTS
+            .iter()
+            .find(|kind| !seen[kind.ordinal() as usize])
+        {
+            return Err(format!("missing weight for {} ants", kind.name()));
+        }
+        if weights.iter().all(|(_, weight)| *weight == 0.0) {
+            return Err("at least one ant kind must have a weight above 0".to_string());
+        }
+        // A config read back from TOML lists kinds in order, so sort to draw the same kinds.
+        let mut weights = weights;
+        weights.sort_by_key(|(kind, _)| kind.ordinal());
+        Ok(Self { weights })
     }
 
     pub fn gen_kind(&self, rng: &mut impl Rng) -> AntKind {
@@ -93,3 +914,475 @@ impl AntKindGenConfig {
             .0
     }
 }
+
+impl TryFrom<BTreeMap<AntKind, f32>> for AntKindGenConfig {
+    type Error = String;
+
+    /// Kinds left out are never drawn.
+    fn try_from(weights: BTreeMap<AntKind, f32>) -> Result<Self, Self::Error> {
+        Self::new(std::array::from_fn(|i| {
+            let kind = AntKind::VARIANTS[i];
+            (kind, weights.get(&kind).copied().unwrap_or(0.0))
+        }))
+    }
+}
+
+impl From<AntKindGenConfig> for BTreeMap<AntKind, f32> {
+    fn from(config: AntKindGenConfig) -> Self {
+        config.weights.into_iter().collect()
+    }
+}
+
+/// How strong a task's stimulus must be before an ant is as likely as not to take it up.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TaskThresholds {
+    pub scout: f32,
+    pub forage: f32,
+    pub nest_work: f32,
+}
+
+/// How long one kind of ant lives, how readily it takes up each task and how it fights.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AntKindConfig {
+    /// Seconds before an ant dies of old age, or 0 if it never does.
+    pub lifespan: f32,
+    pub thresholds: TaskThresholds,
+    pub health: f32,
+    /// Damage dealt per second to an enemy in reach.
+    pub attack: f32,
+    /// Share of incoming damage shrugged off, from 0 to 1.
+    pub armour: f32,
+}
+
+impl AntKindConfig {
+    fn check(&self, kind: AntKind) -> Result<(), String> {
+        let name = kind.name();
+        if self.lifespan.is_nan() || self.lifespan < 0.0 {
+            return Err(format!("lifespan of {name} ants must be at least 0"));
+        }
+        let TaskThresholds {
+            scout,
+            forage,
+            nest_work,
+        } = self.thresholds;
+        if [scout, forage, nest_work]
+            .iter()
+            .any(|threshold| threshold.is_nan() || *threshold < 0.0)
+        {
+            return Err(format!("thresholds of {name} ants must be at least 0"));
+        }
+        if self.health.is_nan() || self.health <= 0.0 {
+            return Err(format!("health of {name} ants must be greater than 0"));
+        }
+        if self.attack.is_nan() || self.attack < 0.0 {
+            return Err(format!("attack of {name} ants must be at least 0"));
+        }
+        if !(0.0..=1.0).contains(&self.armour) {
+            return Err(format!("armour of {name} ants must be between 0 and 1"));
+        }
+        Ok(())
+    }
+}
+
+/// The settings of every [`AntKind`]; kinds or fields left out of a config keep their
+/// defaults.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(
+    try_from = "BTreeMap<AntKind, toml::Table>",
+    into = "BTreeMap<AntKind, AntKindConfig>"
+)]
+pub struct AntKinds([AntKindConfig; AntKind::VARIANT_COUNT]);
+
+impl Default for AntKinds {
+    fn default() -> Self {
+        Self(std::array::from_fn(|i| match AntKind::VARIANTS[i] {
+            AntKind::Scout => AntKindConfig {
+                lifespan: 900.0,
+                thresholds: TaskThresholds {
+                    scout: 0.1,
+                    forage: 0.6,
+                    nest_work: 0.9,
+                },
+                health: 1.0,
+                attack: 0.2,
+                armour: 0.0,
+            },
+            AntKind::Worker => AntKindConfig {
+                lifespan: 1200.0,
+                thresholds: TaskThresholds {
+                    scout: 0.7,
+                    forage: 0.2,
+                    nest_work: 0.4,
+                },
+                health: 1.0,
+                attack: 0.3,
+                armour: 0.1,
+            },
+            // Queens and soldiers never take up tasks.
+            AntKind::Queen => AntKindConfig {
+                lifespan: 0.0,
+                thresholds: TaskThresholds {
+                    scout: 1.0,
+                    forage: 1.0,
+                    nest_work: 1.0,
+                },
+                health: 3.0,
+                attack: 0.2,
+                armour: 0.3,
+            },
+            AntKind::Soldier => AntKindConfig {
+                lifespan: 1200.0,
+                thresholds: TaskThresholds {
+                    scout: 1.0,
+                    forage: 1.0,
+                    nest_work: 1.0,
+                },
+                health: 3.0,
+                attack: 1.0,
+                armour: 0.5,
+            },
+        }))
+    }
+}
+
+impl std::ops::Index<AntKind> for AntKinds {
+    type Output = AntKindConfig;
+
+    fn index(&self, kind: AntKind) -> &Self::Output {
+        &self.0[kind.ordinal() as usize]
+    }
+}
+
+impl TryFrom<BTreeMap<AntKind, toml::Table>> for AntKinds {
+    type Error = String;
+
+    fn try_from(kinds: BTreeMap<AntKind, toml::Table>) -> Result<Self, Self::Error> {
+        let mut result = Self::default();
+        overlay_kinds(&mut result.0, kinds, AntKindConfig::check)?;
+        Ok(result)
+    }
+}
+
+impl From<AntKinds> for BTreeMap<AntKind, AntKindConfig> {
+    fn from(kinds: AntKinds) -> Self {
+        AntKind::VARIANTS.iter().copied().zip(kinds.0).collect()
+    }
+}
+
+/// How one kind of predator moves and catches ants.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PredatorKindConfig {
+    /// Predators of this kind placed when the world is set up.
+    pub count: u32,
+    /// How fast it moves; 0 for predators that lie in wait.
+    pub speed: f32,
+    /// How far away it spots an ant to go after.
+    pub detection_radius: f32,
+    /// How close an ant must come to be caught.
+    pub capture_radius: f32,
+    /// Seconds spent eating a catch before it hunts again.
+    pub handling_time: f32,
+    /// Damage it can take from ants before it dies.
+    pub health: f32,
+    /// Share of incoming damage shrugged off, from 0 to 1.
+    pub armour: f32,
+}
+
+impl PredatorKindConfig {
+    fn check(&self, kind: PredatorKind) -> Result<(), String> {
+        let name = kind.name();
+        if self.speed.is_nan() || self.speed < 0.0 {
+            return Err(format!("speed of {name}s must be at least 0"));
+        }
+        if self.capture_radius.is_nan() || self.capture_radius <= 0.0 {
+            return Err(format!("capture_radius of {name}s must be greater than 0"));
+        }
+        if self.detection_radius.is_nan() || self.detection_radius < self.capture_radius {
+            return Err(format!(
+                "detection_radius of {name}s must be at least their capture_radius"
+            ));
+        }
+        if self.handling_time.is_nan() || self.handling_time < 0.0 {
+            return Err(format!("handling_time of {name}s must be at least 0"));
+        }
+        if self.health.is_nan() || self.health <= 0.0 {
+            return Err(format!("health of {name}s must be greater than 0"));
+        }
+        if !(0.0..=1.0).contains(&self.armour) {
+            return Err(format!("armour of {name}s must be between 0 and 1"));
+        }
+        Ok(())
+    }
+}
+
+/// The settings of every [`PredatorKind`]; kinds or fields left out of a config keep their
+/// defaults.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(
+    try_from = "BTreeMap<PredatorKind, toml::Table>",
+    into = "BTreeMap<PredatorKind, PredatorKindConfig>"
+)]
+pub struct PredatorKinds([PredatorKindConfig; PredatorKind::VARIANT_COUNT]);
+
+impl Default for PredatorKinds {
+    fn default() -> Self {
+        Self(std::array::from_fn(|i| match PredatorKind::VARIANTS[i] {
+            PredatorKind::Spider => PredatorKindConfig {
+                count: 0,
+                speed: 8.0,
+                detection_radius: 40.0,
+                capture_radius: 4.0,
+                handling_time: 20.0,
+                health: 5.0,
+                armour: 0.3,
+            },
+            PredatorKind::Antlion => PredatorKindConfig {
+                count: 0,
+                speed: 0.0,
+                detection_radius: 8.0,
+                capture_radius: 8.0,
+                handling_time: 30.0,
+                health: 8.0,
+                armour: 0.5,
+            },
+        }))
+    }
+}
+
+impl std::ops::Index<PredatorKind> for PredatorKinds {
+    type Output = PredatorKindConfig;
+
+    fn index(&self, kind: PredatorKind) -> &Self::Output {
+        &self.0[kind.ordinal() as usize]
+    }
+}
+
+impl TryFrom<BTreeMap<PredatorKind, toml::Table>> for PredatorKinds {
+    type Error = String;
+
+    fn try_from(kinds: BTreeMap<PredatorKind, toml::Table>) -> Result<Self, Self::Error> {
+        let mut result = Self::default();
+        overlay_kinds(&mut result.0, kinds, PredatorKindConfig::check)?;
+        Ok(result)
+    }
+}
+
+impl From<PredatorKinds> for BTreeMap<PredatorKind, PredatorKindConfig> {
+    fn from(kinds: PredatorKinds) -> Self {
+        PredatorKind::VARIANTS
+            .iter()
+            .copied()
+            .zip(kinds.0)
+            .collect()
+    }
+}
+
+/// Turns ants of kind `from` into kind `to` once they are old enough, while the colony is
+/// short of `to` ants.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct KindChange {
+    pub from: AntKind,
+    pub to: AntKind,
+    /// Seconds an ant must have lived before it changes.
+    #[serde(default)]
+    pub min_age: f32,
+    /// Ants change only while `to` ants make up less than this share of their colony.
+    #[serde(default = "KindChange::default_max_share")]
+    pub max_share: f32,
+}
+
+impl KindChange {
+    fn default_max_share() -> f32 {
+        1.0
+    }
+}
+
+/// How one pheromone behaves once laid down and how it is drawn.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PheromoneChannel {
+    /// Fraction of the pheromone left after one second.
+    pub decay: f32,
+    /// Fraction of a cell's pheromone given to each neighbour per tick.
+    pub diffusion: f32,
+    /// Most pheromone a cell can hold from deposits.
+    pub cap: f32,
+    pub color: [u8; 3],
+}
+
+impl PheromoneChannel {
+    fn check(&self, pheromone: Pheromone) -> Result<(), String> {
+        let name = pheromone.name();
+        if !(self.decay > 0.0 && self.decay <= 1.0) {
+            return Err(format!(
+                "decay of the {name} pheromone must be greater than 0 and at most 1"
+            ));
+        }
+        // Above 0.25 a cell gives away more than it holds and the field oscillates.
+        if !(0.0..=0.25).contains(&self.diffusion) {
+            return Err(format!(
+                "diffusion of the {name} pheromone must be between 0 and 0.25"
+            ));
+        }
+        if self.cap.is_nan() || self.cap <= 0.0 {
+            return Err(format!(
+                "cap of the {name} pheromone must be greater than 0"
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// The channel settings of every [`Pheromone`]; channels or fields left out of a config keep
+/// their defaults.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(
+    try_from = "BTreeMap<Pheromone, toml::Table>",
+    into = "BTreeMap<Pheromone, PheromoneChannel>"
+)]
+pub struct PheromoneChannels([PheromoneChannel; Pheromone::VARIANT_COUNT]);
+
+impl Default for PheromoneChannels {
+    fn default() -> Self {
+        Self(std::array::from_fn(|i| match Pheromone::VARIANTS[i] {
+            Pheromone::Food => PheromoneChannel {
+                decay: 0.99,
+                diffusion: 0.001,
+                cap: 1.0,
+                color: [0, 255, 0],
+            },
+            Pheromone::Nest => PheromoneChannel {
+                decay: 0.99,
+                diffusion: 0.001,
+                cap: 1.0,
+                color: [255, 0, 0],
+            },
+            // Alarm fades within seconds but spreads fast to reach ants around the trouble.
+            Pheromone::Alarm => PheromoneChannel {
+                decay: 0.3,
+                diffusion: 0.05,
+                cap: 1.0,
+                color: [255, 255, 0],
+            },
+        }))
+    }
+}
+
+impl std::ops::Index<Pheromone> for PheromoneChannels {
+    type Output = PheromoneChannel;
+
+    fn index(&self, pheromone: Pheromone) -> &Self::Output {
+        &self.0[pheromone.ordinal() as usize]
+    }
+}
+
+impl TryFrom<BTreeMap<Pheromone, toml::Table>> for PheromoneChannels {
+    type Error = String;
+
+    fn try_from(channels: BTreeMap<Pheromone, toml::Table>) -> Result<Self, Self::Error> {
+        let mut result = Self::default();
+        overlay_kinds(&mut result.0, channels, PheromoneChannel::check)?;
+        Ok(result)
+    }
+}
+
+impl From<PheromoneChannels> for BTreeMap<Pheromone, PheromoneChannel> {
+    fn from(channels: PheromoneChannels) -> Self {
+        Pheromone::VARIANTS
+            .iter()
+            .copied()
+            .zip(channels.0)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layer(name: &str, text: &str) -> ConfigLayer {
+        ConfigLayer {
+            name: name.to_string(),
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn kind_fields_left_out_keep_that_kinds_defaults() {
+        let config = SimulationConfig::from_layers(&[
+            layer("--set", "pheromones.nest.decay = 0.9"),
+            layer("--set", "food_kinds.seeds.energy = 2.0"),
+            layer("--set", "ant_kinds.worker.thresholds.scout = 0.25"),
+            layer("--set", "predators.spider.count = 40"),
+        ])
+        .unwrap_or_else(|errors| panic!("{}", errors[0]));
+        let defaults = PheromoneChannels::default();
+        assert_eq!(config.pheromones[Pheromone::Nest].decay, 0.9);
+        assert_eq!(
+            config.pheromones[Pheromone::Nest].diffusion,
+            defaults[Pheromone::Nest].diffusion
+        );
+        assert_eq!(
+            config.pheromones[Pheromone::Food].decay,
+            defaults[Pheromone::Food].decay
+        );
+        assert_eq!(config.food_kinds[FoodKind::Seeds].energy, 2.0);
+        assert_eq!(
+            config.food_kinds[FoodKind::Seeds].weight,
+            FoodKinds::default()[FoodKind::Seeds].weight
+        );
+        let worker = &config.ant_kinds[AntKind::Worker];
+        let defaults = &AntKinds::default()[AntKind::Worker];
+        assert_eq!(worker.thresholds.scout, 0.25);
+        assert_eq!(worker.thresholds.forage, defaults.thresholds.forage);
+        assert_eq!(worker.lifespan, defaults.lifespan);
+        let spider = &config.predators[PredatorKind::Spider];
+        assert_eq!(spider.count, 40);
+        assert_eq!(
+            spider.speed,
+            PredatorKinds::default()[PredatorKind::Spider].speed
+        );
+    }
+
+    #[test]
+    fn kind_tables_are_still_checked() {
+        let errors =
+            SimulationConfig::from_layers(&[layer("--set", "pheromones.food.diffusion = 0.5")])
+                .err()
+                .unwrap();
+        assert!(errors[0]
+            .to_string()
+            .contains("diffusion of the food pheromone"));
+        let errors = SimulationConfig::from_layers(&[layer("--set", "pheromones.food.decy = 0.5")])
+            .err()
+            .unwrap();
+        assert!(errors[0].to_string().contains("unknown field `decy`"));
+    }
+
+    #[test]
+    fn nested_overrides_keep_the_rest_of_the_table() {
+        let config = SimulationConfig::from_layers(&[
+            layer("file", "[food]\namount = [10.0, 20.0]\npolicy = \"none\"\n"),
+            layer("--set", "food.patches = 2"),
+        ])
+        .unwrap_or_else(|errors| panic!("{}", errors[0]));
+        assert_eq!(config.food.amount, [10.0, 20.0]);
+        assert!(config.food.policy == FoodPolicy::None);
+        assert_eq!(config.food.patches, 2);
+    }
+
+    #[test]
+    fn spatial_cells_are_capped_by_the_world_size() {
+        let errors = SimulationConfig::from_layers(&[layer("--set", "spatial_cell_size = 0.0001")])
+            .err()
+            .unwrap();
+        assert!(errors[0].to_string().contains("spatial_cell_size"));
+        // 1920 / 1024 is just under 2.
+        SimulationConfig::from_layers(&[layer("--set", "spatial_cell_size = 2.0")])
+            .unwrap_or_else(|errors| panic!("{}", errors[0]));
+    }
+}
@@ -0,0 +1,236 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ant::{Ant, AntKind},
+    config::SimulationConfig,
+    obstacle::Obstacles,
+    queen::Flight,
+    spatial::SpatialIndex,
+};
+
+/// How many ants were close in front of an ant after it last walked, which slows its next
+/// step.
+#[derive(Component, Clone, Default, Serialize, Deserialize)]
+pub struct Crowding {
+    ahead: u32,
+}
+
+impl Crowding {
+    pub fn ahead(&self) -> u32 {
+        self.ahead
+    }
+
+    /// The share of its full speed the ant can walk at.
+    pub fn speed_factor(&self, simulation_config: &SimulationConfig) -> f32 {
+        1.0 / (1.0 + simulation_config.crowding.slowdown * self.ahead as f32)
+    }
+}
+
+type JostleData = (
+    Entity,
+    &'static mut Transform,
+    &'static mut Crowding,
+    &'static AntKind,
+);
+
+/// Pushes overlapping ants apart and counts the ants in front of each one. Pushes are worked
+/// out before any ant moves, so the outcome doesn't depend on the order ants are visited in.
+/// Queens hold their ground but still push others aside.
+pub fn separate_ants(
+    simulation_config: Res<SimulationConfig>,
+    obstacles: Res<Obstacles>,
+    ant_index: Res<SpatialIndex<Ant>>,
+    mut ants: Query<JostleData, (With<Ant>, Without<Flight>)>,
+) {
+    let crowding_config = &simulation_config.crowding;
+    let min_distance = crowding_config.body_radius * 2.0;
+    let reach = crowding_config.radius.max(min_distance);
+
+    let mut jostles = vec![];
+    for (entity, transform, _, kind) in ants.iter() {
+        let position = transform.translation.xy();
+        let forward = transform.up().xy();
+        // Summed in order of position, since the index's order depends on its history.
+        let mut neighbours: Vec<Vec2> = ant_index
+            .within(position, reach)
+            .filter(|(other, _)| *other != entity && ants.contains(*other))
+            .map(|(_, other_position)| other_position)
+            .collect();
+        neighbours.sort_by(|a, b| a.x.total_cmp(&b.x).then(a.y.total_cmp(&b.y)));
+        let mut push = Vec2::ZERO;
+        let mut ahead = 0;
+        for other_position in neighbours {
+            let offset = position - other_position;
+            let distance = offset.length();
+            if distance < crowding_config.radius && offset.dot(forward) < 0.0 {
+                ahead += 1;
+            }
+            if distance < min_distance {
+                // Ants on the very same spot back off the way they came.
+                let away = offset.try_normalize().unwrap_or(-forward);
+                push += away * (min_distance - distance) / 2.0;
+            }
+        }
+        if *kind == AntKind::Queen {
+            push = Vec2::ZERO;
+        }
+        jostles.push((entity, push * crowding_config.stiffness, ahead));
+    }
+
+    for (entity, push, ahead) in jostles {
+        let (_, mut transform, mut crowding, _) = ants.get_mut(entity).unwrap();
+        crowding.ahead = ahead;
+        let next = transform.translation.xy() + push;
+        if push != Vec2::ZERO && !obstacles.is_blocked(next) {
+            transform.translation = next.extend(transform.translation.z);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+    use crate::{
+        ant::spawn_ant, config::CrowdingConfig, nest::Colony, spatial::update_spatial_index,
+    };
+
+    fn crowding_world(crowding: CrowdingConfig, ants: &[(Vec2, AntKind)]) -> (World, Schedule) {
+        let mut world = World::new();
+        let simulation_config = SimulationConfig {
+            crowding,
+            ..default()
+        };
+        world.insert_resource(SpatialIndex::<Ant>::from_config(&simulation_config));
+        world.insert_resource(Obstacles::from_config(&simulation_config));
+        world.insert_resource(simulation_config);
+        for &(position, kind) in ants {
+            world.run_system_once(
+                move |mut commands: Commands, simulation_config: Res<SimulationConfig>| {
+                    spawn_ant(
+                        &mut commands,
+                        &simulation_config,
+                        position.x,
+                        position.y,
+                        0.0,
+                        kind,
+                        Colony(0),
+                    );
+                },
+            );
+        }
+        let mut schedule = Schedule::default();
+        schedule.add_systems((update_spatial_index::<Ant>, separate_ants).chain());
+        (world, schedule)
+    }
+
+    /// Each ant's position and the number of ants ahead of it, in order of position.
+    fn ants(world: &mut World) -> Vec<(Vec2, u32)> {
+        let mut ants: Vec<_> = world
+            .query::<(&Transform, &Crowding)>()
+            .iter(world)
+            .map(|(transform, crowding)| (transform.translation.xy(), crowding.ahead()))
+            .collect();
+        ants.sort_by(|(a, _), (b, _)| a.x.total_cmp(&b.x).then(a.y.total_cmp(&b.y)));
+        ants
+    }
+
+    #[test]
+    fn overlapping_ants_are_pushed_apart() {
+        let crowding = CrowdingConfig {
+            stiffness: 1.0,
+            ..default()
+        };
+        let min_distance = crowding.body_radius * 2.0;
+        let (mut world, mut schedule) = crowding_world(
+            crowding,
+            &[
+                (Vec2::new(0.0, 0.0), AntKind::Worker),
+                (Vec2::new(1.0, 0.5), AntKind::Worker),
+            ],
+        );
+
+        schedule.run(&mut world);
+        let ants = ants(&mut world);
+        let distance = ants[0].0.distance(ants[1].0);
+        assert!(distance >= min_distance - 1e-4, "{distance}");
+        // Both moved the same way along the line between them.
+        let middle = (ants[0].0 + ants[1].0) / 2.0;
+        assert!(middle.distance(Vec2::new(0.5, 0.25)) < 1e-4);
+    }
+
+    #[test]
+    fn softer_pushes_separate_ants_over_a_few_ticks() {
+        let min_distance = CrowdingConfig::default().body_radius * 2.0;
+        let (mut world, mut schedule) = crowding_world(
+            default(),
+            &[
+                (Vec2::new(0.0, 0.0), AntKind::Worker),
+                (Vec2::new(0.0, -0.5), AntKind::Worker),
+            ],
+        );
+
+        let mut distances = vec![];
+        for _ in 0..20 {
+            schedule.run(&mut world);
+            let ants = ants(&mut world);
+            distances.push(ants[0].0.distance(ants[1].0));
+        }
+        assert!(distances.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert!(distances[19] >= min_distance - 1e-4, "{distances:?}");
+    }
+
+    #[test]
+    fn queens_hold_their_ground() {
+        let (mut world, mut schedule) = crowding_world(
+            default(),
+            &[
+                (Vec2::new(0.0, 0.0), AntKind::Queen),
+                (Vec2::new(1.0, 0.0), AntKind::Worker),
+            ],
+        );
+
+        schedule.run(&mut world);
+        let ants = ants(&mut world);
+        assert_eq!(ants[0].0, Vec2::ZERO);
+        assert!(ants[1].0.x > 1.0);
+    }
+
+    #[test]
+    fn only_ants_in_front_count_as_ahead() {
+        // Ants face up, so the one higher up has the other behind it.
+        let (mut world, mut schedule) = crowding_world(
+            default(),
+            &[
+                (Vec2::new(0.0, 0.0), AntKind::Worker),
+                (Vec2::new(0.0, 6.0), AntKind::Worker),
+            ],
+        );
+
+        schedule.run(&mut world);
+        let ants = ants(&mut world);
+        assert_eq!(ants[0], (Vec2::new(0.0, 0.0), 1));
+        assert_eq!(ants[1], (Vec2::new(0.0, 6.0), 0));
+    }
+
+    #[test]
+    fn separation_does_not_depend_on_visit_order() {
+        let crowd = [
+            (Vec2::new(0.0, 0.0), AntKind::Worker),
+            (Vec2::new(1.0, 0.5), AntKind::Worker),
+            (Vec2::new(-0.5, 1.5), AntKind::Scout),
+            (Vec2::new(0.5, -1.0), AntKind::Queen),
+            (Vec2::new(2.0, 2.0), AntKind::Soldier),
+        ];
+        let reversed: Vec<_> = crowd.iter().rev().copied().collect();
+        let (mut forward, mut forward_schedule) = crowding_world(default(), &crowd);
+        let (mut backward, mut backward_schedule) = crowding_world(default(), &reversed);
+        for _ in 0..5 {
+            forward_schedule.run(&mut forward);
+            backward_schedule.run(&mut backward);
+            assert_eq!(ants(&mut forward), ants(&mut backward));
+        }
+    }
+}
@@ -2,19 +2,80 @@ use bevy::{
     prelude::*,
     sprite::{MaterialMesh2dBundle, Mesh2dHandle},
 };
+use enum_ordinalize::Ordinalize;
 use rand::prelude::*;
+use serde::{Deserialize, Serialize};
 
 use crate::{
     assets::{Colors, Meshes},
-    config::{LAYER_FOOD, WORLD_HEIGHT, WORLD_WIDTH},
+    config::{FoodKinds, SimulationConfig, LAYER_FOOD},
+    obstacle::Obstacles,
+    rng::SimulationRng,
 };
 
-#[derive(Component)]
+#[derive(
+    Ordinalize, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum FoodKind {
+    #[default]
+    Sugar,
+    Protein,
+    Seeds,
+}
+
+impl FoodKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            FoodKind::Sugar => "sugar",
+            FoodKind::Protein => "protein",
+            FoodKind::Seeds => "seeds",
+        }
+    }
+}
+
+/// How food is replenished as the colonies eat it.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FoodPolicy {
+    /// Every emptied pile is replaced by a new one, keeping the number of piles fixed.
+    #[default]
+    Respawn,
+    /// New piles arrive at random times, `arrival_rate` per second on average, up to
+    /// `max_piles`.
+    Poisson,
+    /// Piles never run out for good; emptied ones grow back at `regrowth_rate`.
+    Regrowing,
+    /// Like `respawn`, until `total_supply` has been handed out.
+    Finite,
+    /// Emptied piles are gone for good.
+    None,
+}
+
+/// Where random piles are placed.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FoodLayout {
+    /// Anywhere in the world.
+    #[default]
+    Uniform,
+    /// Within `patch_radius` of one of `patches` fixed patch centers.
+    Clustered,
+}
+
+#[derive(Component, Clone, Serialize, Deserialize)]
 pub struct Food {
+    kind: FoodKind,
     amount: f32,
+    /// The amount the pile started with, which regrowing piles return to.
+    capacity: f32,
 }
 
 impl Food {
+    pub fn kind(&self) -> FoodKind {
+        self.kind
+    }
+
     pub fn empty(&self) -> bool {
         self.amount <= 0.0
     }
@@ -32,26 +93,274 @@ impl Food {
         self.amount -= amount;
         amount
     }
+
+    pub fn full(&self) -> bool {
+        self.amount >= self.capacity
+    }
+
+    pub fn regrow(&mut self, amount: f32) {
+        self.amount = (self.amount + amount).min(self.capacity);
+    }
+}
+
+/// Marks food left by a killed ant, which the food policy clears once eaten but never
+/// replaces or regrows.
+#[derive(Component)]
+pub struct Corpse;
+
+/// A pile of food placed when the world is set up.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FoodPile {
+    pub position: [f32; 2],
+    #[serde(default)]
+    pub kind: FoodKind,
+    pub amount: f32,
+}
+
+/// Food stored in a nest, in units of each kind.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct FoodStore([f32; FoodKind::VARIANT_COUNT]);
+
+impl FoodStore {
+    pub fn total(&self) -> f32 {
+        self.0.iter().sum()
+    }
+
+    pub fn energy(&self, food_kinds: &FoodKinds) -> f32 {
+        FoodKind::VARIANTS
+            .iter()
+            .map(|&kind| self[kind] * food_kinds[kind].energy)
+            .sum()
+    }
+
+    /// Takes up to `energy` worth of food, eating kinds in the order they're declared, and
+    /// returns the energy taken.
+    pub fn take_energy(&mut self, energy: f32, food_kinds: &FoodKinds) -> f32 {
+        let mut taken = 0.0;
+        for &kind in FoodKind::VARIANTS {
+            let energy_per_unit = food_kinds[kind].energy;
+            let units = self[kind].min((energy - taken) / energy_per_unit);
+            self[kind] -= units;
+            taken += units * energy_per_unit;
+        }
+        taken
+    }
+
+    /// Loses each kind's share of spoiled food over `seconds`.
+    pub fn spoil(&mut self, food_kinds: &FoodKinds, seconds: f32) {
+        for &kind in FoodKind::VARIANTS {
+            self[kind] *= (1.0 - food_kinds[kind].spoilage_rate).powf(seconds);
+        }
+    }
 }
 
-pub fn spawn_food(commands: &mut Commands, x: f32, y: f32, amount: f32) -> Entity {
+impl std::ops::Index<FoodKind> for FoodStore {
+    type Output = f32;
+
+    fn index(&self, kind: FoodKind) -> &Self::Output {
+        &self.0[kind.ordinal() as usize]
+    }
+}
+
+impl std::ops::IndexMut<FoodKind> for FoodStore {
+    fn index_mut(&mut self, kind: FoodKind) -> &mut Self::Output {
+        &mut self.0[kind.ordinal() as usize]
+    }
+}
+
+/// What the food policy remembers between ticks.
+#[derive(Resource, Clone, Default, Serialize, Deserialize)]
+pub struct FoodSupply {
+    /// Centers of the patches clustered piles are placed in.
+    patches: Vec<Vec2>,
+    /// Food still to be handed out under the finite policy.
+    remaining: f32,
+}
+
+impl FoodSupply {
+    pub fn new(
+        simulation_config: &SimulationConfig,
+        obstacles: &Obstacles,
+        rng: &mut impl Rng,
+    ) -> Self {
+        let food_config = &simulation_config.food;
+        let patches = match food_config.layout {
+            FoodLayout::Uniform => vec![],
+            FoodLayout::Clustered => (0..food_config.patches)
+                .filter_map(|_| random_open_position(simulation_config, obstacles, rng))
+                .collect(),
+        };
+        Self {
+            patches,
+            remaining: food_config.total_supply,
+        }
+    }
+
+    pub fn remaining(&self) -> f32 {
+        self.remaining
+    }
+}
+
+pub fn spawn_food(commands: &mut Commands, x: f32, y: f32, kind: FoodKind, amount: f32) -> Entity {
     commands
         .spawn((
-            Food { amount },
+            Food {
+                kind,
+                amount,
+                capacity: amount,
+            },
             SpatialBundle::from_transform(Transform::from_translation(Vec3::new(x, y, 0.0))),
         ))
         .id()
 }
 
-pub fn spawn_random_food(commands: &mut Commands) {
-    let mut rng = rand::thread_rng();
+/// How many random spots are tried before giving up on placing something.
+const SPAWN_ATTEMPTS: u32 = 1000;
+
+/// The edges of the area random food is placed in, kept away from the world's borders.
+fn food_bounds(simulation_config: &SimulationConfig) -> Vec2 {
     let min_distance_from_edge = 60.0;
-    let half_height = WORLD_HEIGHT / 2.0 - min_distance_from_edge;
-    let half_width = WORLD_WIDTH / 2.0 - min_distance_from_edge;
-    let x = rng.gen_range(-half_width..half_width);
-    let y = rng.gen_range(-half_height..half_height);
-    let amount = rng.gen_range(50.0..250.0);
-    spawn_food(commands, x, y, amount);
+    let half_size = Vec2::new(
+        simulation_config.world_width / 2.0,
+        simulation_config.world_height / 2.0,
+    );
+    // Small worlds keep at least the middle half open.
+    half_size - Vec2::splat(min_distance_from_edge).min(half_size / 2.0)
+}
+
+/// A random spot away from the edges that isn't inside an obstacle, or `None` if none turned up
+/// within [`SPAWN_ATTEMPTS`] tries because obstacles cover (almost) all of the area.
+pub(crate) fn random_open_position(
+    simulation_config: &SimulationConfig,
+    obstacles: &Obstacles,
+    rng: &mut impl Rng,
+) -> Option<Vec2> {
+    let bounds = food_bounds(simulation_config);
+    (0..SPAWN_ATTEMPTS)
+        .map(|_| {
+            let x = rng.gen_range(-bounds.x..bounds.x);
+            let y = rng.gen_range(-bounds.y..bounds.y);
+            Vec2::new(x, y)
+        })
+        .find(|&position| !obstacles.is_blocked(position))
+}
+
+/// Spawns a pile at a random spot allowed by the food layout, unless a finite supply has run
+/// out or no open spot was found. Returns whether a pile was spawned.
+pub fn spawn_random_food(
+    commands: &mut Commands,
+    simulation_config: &SimulationConfig,
+    obstacles: &Obstacles,
+    supply: &mut FoodSupply,
+    rng: &mut impl Rng,
+) -> bool {
+    let food_config = &simulation_config.food;
+    let finite = food_config.policy == FoodPolicy::Finite;
+    if finite && supply.remaining <= 0.0 {
+        return false;
+    }
+
+    let position = match food_config.layout {
+        FoodLayout::Uniform => random_open_position(simulation_config, obstacles, rng),
+        FoodLayout::Clustered if supply.patches.is_empty() => None,
+        FoodLayout::Clustered => {
+            let bounds = food_bounds(simulation_config);
+            (0..SPAWN_ATTEMPTS)
+                .map(|_| {
+                    let patch = supply.patches[rng.gen_range(0..supply.patches.len())];
+                    let angle = rng.gen_range(0.0..std::f32::consts::TAU);
+                    let distance = food_config.patch_radius * rng.gen::<f32>().sqrt();
+                    (patch + Vec2::from_angle(angle) * distance).clamp(-bounds, bounds)
+                })
+                .find(|&position| !obstacles.is_blocked(position))
+        }
+    };
+    let Some(position) = position else {
+        return false;
+    };
+    let [min_amount, max_amount] = food_config.amount;
+    let mut amount = if min_amount < max_amount {
+        rng.gen_range(min_amount..max_amount)
+    } else {
+        min_amount
+    };
+    if finite {
+        amount = amount.min(supply.remaining);
+        supply.remaining -= amount;
+    }
+    let kind = simulation_config.food_kinds.gen_kind(rng);
+    spawn_food(commands, position.x, position.y, kind, amount);
+    true
+}
+
+/// A Poisson-distributed count with the given mean, by Knuth's method, which is fine for the
+/// small means of a single tick.
+fn poisson(rng: &mut impl Rng, mean: f32) -> u32 {
+    let limit = (-mean).exp();
+    let mut count = 0;
+    let mut product = rng.gen::<f32>();
+    while product > limit {
+        count += 1;
+        product *= rng.gen::<f32>();
+    }
+    count
+}
+
+/// Clears out emptied piles and adds new ones as the food policy says.
+pub fn replenish_food(
+    mut commands: Commands,
+    simulation_config: Res<SimulationConfig>,
+    obstacles: Res<Obstacles>,
+    mut supply: ResMut<FoodSupply>,
+    mut rng: ResMut<SimulationRng>,
+    mut food: Query<(Entity, &mut Food, Has<Corpse>)>,
+) {
+    let food_config = &simulation_config.food;
+    if food_config.policy == FoodPolicy::Regrowing {
+        let regrowth = food_config.regrowth_rate * simulation_config.fixed_delta_time();
+        for (entity, mut food, corpse) in food.iter_mut() {
+            if corpse {
+                if food.empty() {
+                    commands.entity(entity).despawn_recursive();
+                }
+            } else if !food.full() {
+                food.regrow(regrowth);
+            }
+        }
+        return;
+    }
+
+    let mut piles = 0;
+    let mut emptied = 0;
+    for (entity, food, corpse) in food.iter() {
+        if food.empty() {
+            commands.entity(entity).despawn_recursive();
+            if !corpse {
+                emptied += 1;
+            }
+        } else if !corpse {
+            piles += 1;
+        }
+    }
+
+    let arrivals = match food_config.policy {
+        FoodPolicy::Respawn | FoodPolicy::Finite => emptied,
+        FoodPolicy::Poisson => {
+            let mean = food_config.arrival_rate * simulation_config.fixed_delta_time();
+            poisson(&mut *rng, mean).min(food_config.max_piles.saturating_sub(piles))
+        }
+        FoodPolicy::Regrowing | FoodPolicy::None => 0,
+    };
+    for _ in 0..arrivals {
+        spawn_random_food(
+            &mut commands,
+            &simulation_config,
+            &obstacles,
+            &mut supply,
+            &mut *rng,
+        );
+    }
 }
 
 pub fn setup_food_rendering(
@@ -64,7 +373,7 @@ pub fn setup_food_rendering(
         commands.entity(entity).with_children(|parent| {
             parent.spawn((MaterialMesh2dBundle {
                 mesh: meshes.food.clone(),
-                material: colors.food.clone(),
+                material: colors.food(food.kind),
                 transform: Transform::from_translation(Vec3::Z * LAYER_FOOD)
                     .with_scale(Vec3::splat((food.amount / std::f32::consts::PI).sqrt())),
                 ..Default::default()
@@ -86,3 +395,278 @@ pub fn update_food_size(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::world::CommandQueue;
+
+    use super::*;
+    use crate::{config::FoodConfig, obstacle::Obstacle};
+
+    /// A world with default settings except for `food_config`, and a schedule that replenishes
+    /// its food once per run.
+    fn food_world(food_config: FoodConfig) -> (World, Schedule) {
+        let simulation_config = SimulationConfig {
+            food: food_config,
+            ..default()
+        };
+        let obstacles = Obstacles::from_config(&simulation_config);
+        let mut rng = SimulationRng::new(Some(1));
+        let supply = FoodSupply::new(&simulation_config, &obstacles, &mut rng);
+
+        let mut world = World::new();
+        world.insert_resource(simulation_config);
+        world.insert_resource(obstacles);
+        world.insert_resource(rng);
+        world.insert_resource(supply);
+        let mut schedule = Schedule::default();
+        schedule.add_systems(replenish_food);
+        (world, schedule)
+    }
+
+    fn add_pile(world: &mut World, x: f32, amount: f32, capacity: f32) -> Entity {
+        world
+            .spawn((
+                Food {
+                    kind: FoodKind::Sugar,
+                    amount,
+                    capacity,
+                },
+                Transform::from_xyz(x, 0.0, 0.0),
+            ))
+            .id()
+    }
+
+    /// The amounts of the piles that aren't corpses, smallest first.
+    fn piles(world: &mut World) -> Vec<f32> {
+        let mut amounts: Vec<_> = world
+            .query_filtered::<&Food, Without<Corpse>>()
+            .iter(world)
+            .map(|food| food.amount)
+            .collect();
+        amounts.sort_by(f32::total_cmp);
+        amounts
+    }
+
+    #[test]
+    fn respawn_replaces_every_emptied_pile() {
+        let (mut world, mut schedule) = food_world(FoodConfig {
+            policy: FoodPolicy::Respawn,
+            ..default()
+        });
+        add_pile(&mut world, 0.0, 0.0, 100.0);
+        add_pile(&mut world, 10.0, 0.0, 100.0);
+        add_pile(&mut world, 20.0, 100.0, 100.0);
+
+        for _ in 0..3 {
+            schedule.run(&mut world);
+            let piles = piles(&mut world);
+            assert_eq!(piles.len(), 3);
+            assert!(piles.iter().all(|amount| *amount > 0.0));
+        }
+    }
+
+    #[test]
+    fn none_clears_emptied_piles_for_good() {
+        let (mut world, mut schedule) = food_world(FoodConfig {
+            policy: FoodPolicy::None,
+            ..default()
+        });
+        add_pile(&mut world, 0.0, 0.0, 100.0);
+        add_pile(&mut world, 10.0, 0.0, 100.0);
+        add_pile(&mut world, 20.0, 100.0, 100.0);
+
+        for _ in 0..3 {
+            schedule.run(&mut world);
+            assert_eq!(piles(&mut world), [100.0]);
+        }
+    }
+
+    #[test]
+    fn finite_supply_runs_out() {
+        let (mut world, mut schedule) = food_world(FoodConfig {
+            policy: FoodPolicy::Finite,
+            amount: [100.0, 100.0],
+            total_supply: 150.0,
+            ..default()
+        });
+        for x in [0.0, 10.0, 20.0] {
+            add_pile(&mut world, x, 0.0, 100.0);
+        }
+
+        // The last pile handed out gets what is left, and the third gets nothing.
+        schedule.run(&mut world);
+        assert_eq!(piles(&mut world), [50.0, 100.0]);
+        assert_eq!(world.resource::<FoodSupply>().remaining(), 0.0);
+
+        for mut food in world.query::<&mut Food>().iter_mut(&mut world) {
+            food.amount = 0.0;
+        }
+        for _ in 0..3 {
+            schedule.run(&mut world);
+            assert!(piles(&mut world).is_empty());
+        }
+    }
+
+    #[test]
+    fn poisson_arrivals_stop_at_max_piles() {
+        let (mut world, mut schedule) = food_world(FoodConfig {
+            policy: FoodPolicy::Poisson,
+            // About one pile per tick.
+            arrival_rate: 60.0,
+            max_piles: 5,
+            ..default()
+        });
+
+        let mut counts = vec![];
+        for _ in 0..60 {
+            schedule.run(&mut world);
+            counts.push(piles(&mut world).len());
+        }
+        assert!(counts.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert_eq!(counts.last(), Some(&5));
+    }
+
+    #[test]
+    fn regrowing_piles_refill_up_to_their_capacity() {
+        let (mut world, mut schedule) = food_world(FoodConfig {
+            policy: FoodPolicy::Regrowing,
+            // One unit per tick.
+            regrowth_rate: 60.0,
+            ..default()
+        });
+        let emptied = add_pile(&mut world, 0.0, 0.0, 10.0);
+        let corpse = add_pile(&mut world, 10.0, 0.0, 10.0);
+        world.entity_mut(corpse).insert(Corpse);
+
+        for _ in 0..4 {
+            schedule.run(&mut world);
+        }
+        assert!(world.get_entity(corpse).is_none());
+        let amount = world.get::<Food>(emptied).unwrap().amount;
+        assert!((amount - 4.0).abs() < 1e-4, "{amount}");
+
+        for _ in 0..20 {
+            schedule.run(&mut world);
+        }
+        assert_eq!(piles(&mut world), [10.0]);
+    }
+
+    #[test]
+    fn eaten_corpses_are_cleared_but_not_replaced() {
+        let (mut world, mut schedule) = food_world(FoodConfig {
+            policy: FoodPolicy::Respawn,
+            ..default()
+        });
+        let corpse = add_pile(&mut world, 0.0, 0.0, 5.0);
+        world.entity_mut(corpse).insert(Corpse);
+
+        schedule.run(&mut world);
+        assert!(world.get_entity(corpse).is_none());
+        assert_eq!(world.query::<&Food>().iter(&world).count(), 0);
+    }
+
+    #[test]
+    fn clustered_piles_land_within_a_patch() {
+        let food_config = FoodConfig {
+            policy: FoodPolicy::Respawn,
+            layout: FoodLayout::Clustered,
+            patches: 2,
+            patch_radius: 20.0,
+            ..default()
+        };
+        let (mut world, mut schedule) = food_world(food_config.clone());
+        for i in 0..50 {
+            add_pile(&mut world, i as f32, 0.0, 100.0);
+        }
+        schedule.run(&mut world);
+
+        let patches = world.resource::<FoodSupply>().patches.clone();
+        assert_eq!(patches.len(), 2);
+        let positions: Vec<_> = world
+            .query_filtered::<&Transform, With<Food>>()
+            .iter(&world)
+            .map(|transform| transform.translation.xy())
+            .collect();
+        assert_eq!(positions.len(), 50);
+        for position in positions {
+            assert!(
+                patches
+                    .iter()
+                    .any(|patch| patch.distance(position) <= food_config.patch_radius),
+                "{position} is outside every patch"
+            );
+        }
+    }
+
+    /// Tries to spawn one random pile into `world`, returning whether it did.
+    fn try_spawn(
+        world: &mut World,
+        simulation_config: &SimulationConfig,
+        supply: &mut FoodSupply,
+    ) -> bool {
+        let obstacles = Obstacles::from_config(simulation_config);
+        let mut rng = SimulationRng::new(Some(1));
+        let mut queue = CommandQueue::default();
+        let spawned = spawn_random_food(
+            &mut Commands::new(&mut queue, world),
+            simulation_config,
+            &obstacles,
+            supply,
+            &mut rng,
+        );
+        queue.apply(world);
+        spawned
+    }
+
+    #[test]
+    fn spawning_gives_up_when_obstacles_cover_the_area() {
+        // The nest sits in the strip below the wall, outside the area food is placed in.
+        let mut simulation_config = SimulationConfig {
+            nests: vec![[0.0, -515.0]],
+            obstacles: vec![Obstacle::Rectangle {
+                center: [0.0, 0.0],
+                size: [5000.0, 990.0],
+            }],
+            ..default()
+        };
+        let obstacles = Obstacles::from_config(&simulation_config);
+        let mut rng = SimulationRng::new(Some(1));
+        assert_eq!(
+            random_open_position(&simulation_config, &obstacles, &mut rng),
+            None
+        );
+
+        for layout in [FoodLayout::Uniform, FoodLayout::Clustered] {
+            simulation_config.food.layout = layout;
+            let mut supply = FoodSupply::new(&simulation_config, &obstacles, &mut rng);
+            assert!(supply.patches.is_empty());
+            let mut world = World::new();
+            assert!(!try_spawn(&mut world, &simulation_config, &mut supply));
+            assert_eq!(world.query::<&Food>().iter(&world).count(), 0);
+        }
+    }
+
+    #[test]
+    fn spawning_gives_up_on_a_point_patch_inside_a_wall() {
+        let simulation_config = SimulationConfig {
+            obstacles: vec![Obstacle::Circle {
+                center: [0.0, 0.0],
+                radius: 50.0,
+            }],
+            food: FoodConfig {
+                layout: FoodLayout::Clustered,
+                patch_radius: 0.0,
+                ..default()
+            },
+            ..default()
+        };
+        let mut supply = FoodSupply {
+            patches: vec![Vec2::ZERO],
+            remaining: 0.0,
+        };
+        let mut world = World::new();
+        assert!(!try_spawn(&mut world, &simulation_config, &mut supply));
+        assert_eq!(world.query::<&Food>().iter(&world).count(), 0);
+    }
+}
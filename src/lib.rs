@@ -0,0 +1,20 @@
+pub mod ant;
+pub mod assets;
+pub mod cli;
+pub mod combat;
+pub mod config;
+pub mod crowding;
+pub mod food;
+pub mod metrics;
+pub mod nest;
+pub mod obstacle;
+pub mod predator;
+pub mod queen;
+pub mod rng;
+pub mod run;
+pub mod scenario;
+pub mod snapshot;
+pub mod spatial;
+pub mod task;
+pub mod track;
+pub mod trophallaxis;
@@ -1,47 +1,165 @@
-mod ant;
-mod assets;
-mod config;
-mod food;
-mod nest;
-mod track;
-
-use std::time::Duration;
+use std::{path::Path, time::Duration};
 
 use ant::{
-    decay_satiation, deposit_food, eat_held_food, eat_nest_food, emit_ant_pheromones, pick_up_food,
-    rotate_ants, setup_ant_rendering, spawn_ant, starve, update_ant_holding_food, walk_ants,
-    HeldFood, Satiation,
+    age_ants, decay_satiation, deposit_food, eat_held_food, eat_nest_food, emit_ant_pheromones,
+    pick_up_food, remove_dead_ants, rotate_ants, setup_ant_rendering, spawn_ant, starve,
+    update_ant_holding_food, update_ant_kind_color, walk_ants, Age, AntBorn, AntDied, AntKind,
+    FoodDelivered, HeldFood, Satiation,
+};
+use ant_colony::{
+    ant, assets, cli, combat, config, crowding, food, metrics, nest, obstacle, predator, queen,
+    rng, run, scenario, snapshot, spatial, task, track, trophallaxis,
 };
 use assets::{Colors, Meshes};
 use bevy::{
+    app::ScheduleRunnerPlugin,
     diagnostic::{DiagnosticsPlugin, FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin},
-    ecs::schedule::ScheduleLabel,
-    log::LogPlugin,
+    log::{Level, LogPlugin},
     prelude::*,
     render::camera::ScalingMode,
     sprite::MaterialMesh2dBundle,
-    time::common_conditions::on_real_timer,
+    time::TimeUpdateStrategy,
     window::WindowMode::BorderlessFullscreen,
 };
-use config::{SimulationConfig, LAYER_DIRT, TICKS_PER_SECOND, TICK_RATE_MULTIPLIER};
-use food::{setup_food_rendering, spawn_random_food, update_food_size};
-use nest::{emit_nest_pheromones, setup_nest_rendering, spawn_ants_from_nest, spawn_nest, Nest};
+use clap::Parser;
+use cli::{Cli, Mode};
+use combat::fight;
+use config::{ConfigError, ConfigLayer, SimulationConfig, LAYER_DIRT};
+use crowding::separate_ants;
+use food::{
+    replenish_food, setup_food_rendering, spawn_food, spawn_random_food, update_food_size, Food,
+    FoodSupply,
+};
+use metrics::{collect_metric_events, record_metrics, MetricsFormat, MetricsRecorder};
+use nest::{
+    emit_nest_pheromones, raise_brood, setup_nest_rendering, spawn_nest, spoil_nest_food, Colony,
+    Nest,
+};
+use obstacle::Obstacles;
+use predator::{hunt, setup_predator_rendering, spawn_random_predators};
+use queen::{fly_queens, Flight};
 use rand::prelude::*;
-use track::{
-    decay_tracks, diffuse_tracks, setup_tracks, setup_tracks_renderin, update_tracks_image,
+use rng::SimulationRng;
+use run::{advance_tick, check_run_limits, on_tick_interval, RunLimits, SimulationTick, TickSet};
+use scenario::Scenario;
+use snapshot::{
+    restore_snapshot, save_snapshot_checkpoint, save_snapshot_on_exit, PendingSnapshot,
+    SnapshotOutput, WorldSnapshot,
 };
+use spatial::{update_spatial_index, SpatialIndex};
+use task::allocate_tasks;
+use track::{setup_tracks, setup_tracks_renderin, update_tracks, update_tracks_image};
+use trophallaxis::{share_food, FoodShared};
 
 fn main() {
-    run_simulation(SimulationConfig::default());
+    let cli = Cli::parse();
+    let snapshot = match cli.resume.as_deref().map(WorldSnapshot::load).transpose() {
+        Ok(snapshot) => snapshot,
+        Err(err) => {
+            eprintln!("error: {err}");
+            std::process::exit(1);
+        }
+    };
+    let simulation_config = match load_config(&cli, snapshot.as_ref()) {
+        Ok(simulation_config) => simulation_config,
+        Err(errors) => {
+            for error in errors {
+                eprintln!("error: {error}");
+            }
+            std::process::exit(1);
+        }
+    };
+    if let Some(Err(err)) = snapshot
+        .as_ref()
+        .map(|snapshot| snapshot.check_config(&simulation_config))
+    {
+        eprintln!("error: {err}");
+        std::process::exit(1);
+    }
+    run_simulation(simulation_config, snapshot, cli);
 }
 
-fn run_simulation(simulation_config: SimulationConfig) {
-    let headless = true;
+fn load_config(
+    cli: &Cli,
+    snapshot: Option<&WorldSnapshot>,
+) -> Result<SimulationConfig, Vec<ConfigError>> {
+    let mut layers = vec![];
+    if let (Some(path), Some(snapshot)) = (&cli.resume, snapshot) {
+        layers.push(ConfigLayer {
+            name: format!("{} (snapshot config)", path.display()),
+            text: snapshot.config.clone(),
+        });
+    }
+    if let Some(path) = &cli.config {
+        layers.push(ConfigLayer::from_file(path).map_err(|err| vec![err])?);
+    }
+    let scenario_position = layers.len();
+    layers.extend(cli.overrides.iter().map(|assignment| ConfigLayer {
+        name: format!("--set {assignment}"),
+        text: assignment.clone(),
+    }));
+    if let Some(seed) = cli.seed {
+        layers.push(ConfigLayer {
+            name: "--seed".to_string(),
+            text: format!("seed = {seed}"),
+        });
+    }
+    if let Some(path) = &cli.scenario {
+        // The scenario is stretched over the world, so place it using the config without it.
+        let scenario = Scenario::load(path).map_err(|err| vec![err])?;
+        let layer = scenario
+            .to_layer(
+                format!("{} (scenario)", path.display()),
+                &SimulationConfig::from_layers(&layers)?,
+            )
+            .map_err(|err| vec![err])?;
+        layers.insert(scenario_position, layer);
+    }
+    SimulationConfig::from_layers(&layers)
+}
+
+fn create_metrics_recorder(path: &Path) -> Result<MetricsRecorder, String> {
+    let format = MetricsFormat::from_path(path).ok_or_else(|| {
+        format!(
+            "{}: metrics file must end in .csv or .jsonl",
+            path.display()
+        )
+    })?;
+    MetricsRecorder::create(path, format).map_err(|err| format!("{}: {err}", path.display()))
+}
+
+fn run_simulation(simulation_config: SimulationConfig, snapshot: Option<WorldSnapshot>, cli: Cli) {
     let mut app = create_base_app(simulation_config);
-    if headless {
-        app = augment_headless(app);
-    } else {
-        app = augment_rendering(app);
+    if let Some(snapshot) = snapshot {
+        app.insert_resource(PendingSnapshot(Some(snapshot)));
+    }
+    app.insert_resource(RunLimits {
+        max_ticks: cli.ticks,
+        max_duration: cli.duration,
+        ..default()
+    });
+    app = match cli.mode {
+        Mode::Headless => augment_headless(app, cli.log_level, cli.speed),
+        Mode::Rendered => augment_rendering(app, cli.log_level),
+    };
+    app = add_simulation(app, cli.stats_interval);
+    if let Some(path) = &cli.metrics {
+        match create_metrics_recorder(path) {
+            Ok(recorder) => app = add_metrics(app, recorder, cli.metrics_interval),
+            Err(err) => {
+                eprintln!("error: {err}");
+                std::process::exit(1);
+            }
+        }
+    }
+    if let Some(path) = cli.snapshot {
+        app = add_snapshots(
+            app,
+            SnapshotOutput {
+                path,
+                interval: cli.snapshot_interval,
+            },
+        );
     }
     app.run();
 }
@@ -49,57 +167,156 @@ fn run_simulation(simulation_config: SimulationConfig) {
 fn create_base_app(simulation_config: SimulationConfig) -> App {
     let mut app = App::new();
 
-    app.add_plugins((FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin::default()))
+    app.insert_resource(SimulationRng::new(simulation_config.seed))
+        .insert_resource(SpatialIndex::<Food>::from_config(&simulation_config))
+        .insert_resource(SpatialIndex::<Nest>::from_config(&simulation_config))
+        .insert_resource(SpatialIndex::<ant::Ant>::from_config(&simulation_config))
+        .insert_resource(Obstacles::from_config(&simulation_config))
+        .init_resource::<FoodSupply>()
+        .add_plugins((FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin::default()))
         .insert_resource(simulation_config)
-        .add_systems(Startup, (setup, setup_tracks));
+        .init_resource::<SimulationTick>()
+        .init_resource::<RunLimits>()
+        .add_event::<AntBorn>()
+        .add_event::<AntDied>()
+        .add_event::<FoodDelivered>()
+        .add_event::<FoodShared>()
+        .configure_sets(
+            FixedUpdate,
+            (TickSet::Simulate, TickSet::Record, TickSet::Advance).chain(),
+        )
+        .add_systems(
+            Startup,
+            (
+                (setup, setup_tracks).run_if(not(resource_exists::<PendingSnapshot>)),
+                restore_snapshot.run_if(resource_exists::<PendingSnapshot>),
+            ),
+        )
+        .add_systems(Last, check_run_limits);
     app
 }
 
-fn add_simulation(mut app: App, label: impl ScheduleLabel) -> App {
+fn add_simulation(mut app: App, stats_interval: u64) -> App {
     app.add_systems(
-        label,
+        FixedUpdate,
         ((
             (
-                (decay_tracks, diffuse_tracks, emit_nest_pheromones).chain(),
-                ((decay_satiation, eat_held_food), starve).chain(),
+                (update_tracks, emit_nest_pheromones).chain(),
+                (
+                    decay_satiation,
+                    eat_held_food,
+                    starve,
+                    age_ants,
+                    remove_dead_ants,
+                )
+                    .chain(),
+                spoil_nest_food,
             ),
             (
                 walk_ants,
+                fly_queens,
+                (
+                    update_spatial_index::<Food>,
+                    update_spatial_index::<Nest>,
+                    update_spatial_index::<ant::Ant>,
+                ),
+                // Pushes are small, so the ant index isn't updated again until the next tick.
+                separate_ants,
+                fight,
+                hunt,
+                // These touch the same ants, so run them in a fixed order to stay deterministic.
                 (
                     deposit_food,
                     pick_up_food,
                     emit_ant_pheromones,
                     eat_nest_food,
-                ),
-                (rotate_ants, spawn_ants_from_nest),
+                    share_food,
+                )
+                    .chain(),
+                replenish_food,
+                // Again, so ants sense the food that was just used up or respawned.
+                update_spatial_index::<Food>,
+                (allocate_tasks, rotate_ants, raise_brood).chain(),
             )
                 .chain(),
-            log_stats.run_if(on_real_timer(Duration::from_secs(1))),
+            log_stats.run_if(on_tick_interval(stats_interval)),
         )
-            .chain(),),
-    );
+            .chain()
+            .in_set(TickSet::Simulate),),
+    )
+    .add_systems(FixedUpdate, advance_tick.in_set(TickSet::Advance));
     app
 }
 
-fn augment_headless(mut app: App) -> App {
-    app.add_plugins((MinimalPlugins, LogPlugin::default(), DiagnosticsPlugin));
-    app = add_simulation(app, Update);
+fn add_snapshots(mut app: App, output: SnapshotOutput) -> App {
+    app.insert_resource(output)
+        .add_systems(
+            FixedUpdate,
+            save_snapshot_checkpoint
+                .after(advance_tick)
+                .in_set(TickSet::Advance),
+        )
+        .add_systems(Last, save_snapshot_on_exit.after(check_run_limits));
     app
 }
 
-fn augment_rendering(mut app: App) -> App {
-    app.add_plugins(DefaultPlugins.set(WindowPlugin {
-        primary_window: Some(Window {
-            title: "Ant Colony".into(),
-            mode: BorderlessFullscreen,
+fn add_metrics(mut app: App, recorder: MetricsRecorder, interval: u64) -> App {
+    app.insert_resource(recorder).add_systems(
+        FixedUpdate,
+        (
+            collect_metric_events,
+            record_metrics.run_if(on_tick_interval(interval)),
+        )
+            .chain()
+            .in_set(TickSet::Record),
+    );
+    app
+}
+
+fn augment_headless(mut app: App, log_level: Level, speed: Option<f64>) -> App {
+    app.add_plugins((
+        LogPlugin {
+            level: log_level,
             ..default()
-        }),
-        ..default()
-    }))
+        },
+        DiagnosticsPlugin,
+    ));
+    add_fixed_stepping(app, speed)
+}
+
+/// Advances the simulation by exactly one tick per update, either as fast as possible or at
+/// `speed` times real time.
+fn add_fixed_stepping(mut app: App, speed: Option<f64>) -> App {
+    let simulation_config = app.world().resource::<SimulationConfig>();
+    let tick = Duration::from_secs_f64(1.0 / simulation_config.ticks_per_second);
+    let wait = speed.map_or(Duration::ZERO, |speed| tick.div_f64(speed));
+
+    app.add_plugins(MinimalPlugins.set(ScheduleRunnerPlugin::run_loop(wait)))
+        .insert_resource(Time::<Fixed>::from_duration(tick))
+        .insert_resource(TimeUpdateStrategy::ManualDuration(tick));
+    app
+}
+
+fn augment_rendering(mut app: App, log_level: Level) -> App {
+    let simulation_config = app.world().resource::<SimulationConfig>();
+    let tick_rate = simulation_config.ticks_per_second * simulation_config.tick_rate_multiplier;
+    app.add_plugins(
+        DefaultPlugins
+            .set(WindowPlugin {
+                primary_window: Some(Window {
+                    title: "Ant Colony".into(),
+                    mode: BorderlessFullscreen,
+                    ..default()
+                }),
+                ..default()
+            })
+            .set(LogPlugin {
+                level: log_level,
+                ..default()
+            }),
+    )
     .insert_resource(ClearColor(config::CLEAR_COLOR))
-    .insert_resource(Time::<Fixed>::from_hz(
-        TICKS_PER_SECOND * TICK_RATE_MULTIPLIER,
-    ))
+    .insert_resource(Time::<Fixed>::from_hz(tick_rate))
     .init_resource::<Meshes>()
     .init_resource::<Colors>()
     .add_systems(Startup, setup_rendering)
@@ -107,45 +324,95 @@ fn augment_rendering(mut app: App) -> App {
         Update,
         (
             setup_tracks_renderin,
-            (setup_ant_rendering, update_ant_holding_food).chain(),
+            (
+                setup_ant_rendering,
+                update_ant_holding_food,
+                update_ant_kind_color,
+            )
+                .chain(),
             (setup_food_rendering, update_food_size).chain(),
             setup_nest_rendering,
+            setup_predator_rendering,
             update_tracks_image,
             exit,
         ),
     );
-    app = add_simulation(app, FixedUpdate);
     app
 }
 
 #[derive(Component)]
 struct MainCamera;
 
-fn setup(mut commands: Commands, simulation_config: Res<SimulationConfig>) {
-    let mut rng = rand::thread_rng();
+fn setup(
+    mut commands: Commands,
+    simulation_config: Res<SimulationConfig>,
+    obstacles: Res<Obstacles>,
+    mut rng: ResMut<SimulationRng>,
+    mut supply: ResMut<FoodSupply>,
+) {
+    info!("seed: {}", rng.seed());
 
-    for _ in 0..100 {
-        let x = rng.gen_range(-10.0..10.0);
-        let y = rng.gen_range(-10.0..10.0);
-        let rotation = rng.gen_range(0.0..std::f32::consts::PI * 2.0);
+    for (i, &[nest_x, nest_y]) in simulation_config.nests.iter().enumerate() {
+        let colony = Colony(i as u32);
+        for _ in 0..simulation_config.initial_ants {
+            let x = nest_x + rng.gen_range(-10.0..10.0);
+            let y = nest_y + rng.gen_range(-10.0..10.0);
+            let rotation = rng.gen_range(0.0..std::f32::consts::PI * 2.0);
+            let kind = simulation_config.ant_kind_gen_config.gen_kind(&mut *rng);
+            let ant = spawn_ant(
+                &mut commands,
+                &simulation_config,
+                x,
+                y,
+                rotation,
+                kind,
+                colony,
+            );
+            // The founding workforce is of mixed ages so it doesn't all die at once.
+            let lifespan = simulation_config.ant_kinds[kind].lifespan;
+            commands
+                .entity(ant)
+                .insert(Age::new(rng.gen_range(0.0..0.5) * lifespan));
+            if kind == AntKind::Queen {
+                let flight = Flight::new(&simulation_config, Vec2::new(x, y), &mut *rng);
+                commands.entity(ant).insert(flight);
+            }
+        }
         spawn_ant(
             &mut commands,
             &simulation_config,
-            x,
-            y,
-            rotation,
-            simulation_config.ant_kind_gen_config.gen_kind(&mut rng),
+            nest_x,
+            nest_y,
+            0.0,
+            AntKind::Queen,
+            colony,
         );
+        spawn_nest(&mut commands, nest_x, nest_y, colony);
     }
 
-    for _ in 0..25 {
-        spawn_random_food(&mut commands);
+    for pile in &simulation_config.initial_food {
+        let [x, y] = pile.position;
+        spawn_food(&mut commands, x, y, pile.kind, pile.amount);
     }
-
-    spawn_nest(&mut commands, 0.0, 0.0);
+    *supply = FoodSupply::new(&simulation_config, &obstacles, &mut *rng);
+    for _ in 0..simulation_config.random_food {
+        spawn_random_food(
+            &mut commands,
+            &simulation_config,
+            &obstacles,
+            &mut supply,
+            &mut *rng,
+        );
+    }
+    spawn_random_predators(&mut commands, &simulation_config, &obstacles, &mut *rng);
 }
 
-fn setup_rendering(mut commands: Commands, meshes: Res<Meshes>, colors: Res<Colors>) {
+fn setup_rendering(
+    mut commands: Commands,
+    simulation_config: Res<SimulationConfig>,
+    meshes: Res<Meshes>,
+    colors: Res<Colors>,
+) {
     commands.spawn((
         Camera2dBundle {
             projection: OrthographicProjection {
@@ -153,8 +420,8 @@ fn setup_rendering(mut commands: Commands, meshes: Res<Meshes>, colors: Res<Colo
                 near: -1000.0,
                 scale: 1.0,
                 scaling_mode: ScalingMode::AutoMax {
-                    max_width: config::WORLD_WIDTH,
-                    max_height: config::WORLD_HEIGHT,
+                    max_width: simulation_config.world_width,
+                    max_height: simulation_config.world_height,
                 },
                 ..default()
             },
@@ -186,7 +453,7 @@ fn log_stats(ants: Query<(&HeldFood, &Satiation), With<ant::Ant>>, nests: Query<
     let total_satiation: f32 = ants.iter().map(|(_, satiation)| satiation.amount()).sum();
     let average_satiation = total_satiation / ant_count as f32;
     let nest_count = nests.iter().count();
-    let total_nest_food: f32 = nests.iter().map(|nest| nest.food).sum();
+    let total_nest_food: f32 = nests.iter().map(|nest| nest.food.total()).sum();
     let average_nest_food = total_nest_food / nest_count as f32;
 
     info!("ants: {ant_count}");
@@ -195,3 +462,79 @@ fn log_stats(ants: Query<(&HeldFood, &Satiation), With<ant::Ant>>, nests: Query<
     info!("nests: {nest_count}");
     info!("average nest food: {average_nest_food}");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ant_positions_after(seed: u64, ticks: usize) -> Vec<Vec3> {
+        let mut app = create_base_app(SimulationConfig {
+            seed: Some(seed),
+            ..default()
+        });
+        app = add_fixed_stepping(app, None);
+        app = add_simulation(app, 600);
+        for _ in 0..ticks {
+            app.update();
+        }
+
+        let world = app.world_mut();
+        world
+            .query_filtered::<&Transform, With<ant::Ant>>()
+            .iter(world)
+            .map(|transform| transform.translation)
+            .collect()
+    }
+
+    #[test]
+    fn same_seed_reproduces_ant_positions() {
+        assert_eq!(ant_positions_after(7, 600), ant_positions_after(7, 600));
+    }
+
+    #[test]
+    fn different_seeds_diverge() {
+        assert_ne!(ant_positions_after(7, 60), ant_positions_after(8, 60));
+    }
+
+    /// Runs seed 7 up to tick `until`, from the start or from `snapshot`, saving a checkpoint
+    /// to `path` every 600 ticks.
+    fn run_with_checkpoints(path: &Path, snapshot: Option<WorldSnapshot>, until: u64) {
+        let mut app = create_base_app(SimulationConfig {
+            seed: Some(7),
+            ..default()
+        });
+        if let Some(snapshot) = snapshot {
+            app.insert_resource(PendingSnapshot(Some(snapshot)));
+        }
+        app = add_fixed_stepping(app, None);
+        app = add_simulation(app, 600);
+        app = add_snapshots(
+            app,
+            SnapshotOutput {
+                path: path.display().to_string(),
+                interval: Some(600),
+            },
+        );
+        while app.world().resource::<SimulationTick>().0 < until {
+            app.update();
+        }
+    }
+
+    #[test]
+    fn resuming_from_a_snapshot_matches_an_uninterrupted_run() {
+        let dir = std::env::temp_dir().join(format!("ant_colony_resume_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        run_with_checkpoints(&dir.join("straight_{tick}.bin"), None, 1200);
+        let snapshot = WorldSnapshot::load(&dir.join("straight_600.bin")).unwrap();
+        run_with_checkpoints(&dir.join("resumed_{tick}.bin"), Some(snapshot), 1200);
+
+        let straight = std::fs::read(dir.join("straight_1200.bin")).unwrap();
+        let resumed = std::fs::read(dir.join("resumed_1200.bin")).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(
+            straight == resumed,
+            "snapshots at tick 1200 differ after resuming at tick 600"
+        );
+    }
+}
@@ -0,0 +1,473 @@
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+use enum_ordinalize::Ordinalize;
+use serde::Serialize;
+
+use crate::{
+    ant::{Ant, AntBorn, AntDied, AntKind, DeathCause, FoodDelivered},
+    config::SimulationConfig,
+    crowding::Crowding,
+    food::{Food, FoodKind, FoodPolicy, FoodStore, FoodSupply},
+    nest::{Brood, BroodStage, Colony, Nest},
+    run::SimulationTick,
+    task::Task,
+    track::{Pheromone, Tracks},
+    trophallaxis::FoodShared,
+};
+
+#[derive(Clone, Copy)]
+pub enum MetricsFormat {
+    Csv,
+    JsonLines,
+}
+
+impl MetricsFormat {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "csv" => Some(Self::Csv),
+            "jsonl" | "ndjson" => Some(Self::JsonLines),
+            _ => None,
+        }
+    }
+}
+
+/// One value of one series at one point in time, e.g. the `population` of `worker` ants in
+/// colony 0. World-wide series have no colony.
+#[derive(Serialize)]
+struct MetricRecord<'a> {
+    tick: u64,
+    time: f64,
+    metric: &'a str,
+    colony: Option<u32>,
+    label: &'a str,
+    value: f32,
+}
+
+/// Accumulates colony events between samples and writes every series to a file in long
+/// format, one record per metric and label.
+#[derive(Resource)]
+pub struct MetricsRecorder {
+    format: MetricsFormat,
+    writer: BufWriter<File>,
+    births: BTreeMap<Colony, [u64; AntKind::VARIANT_COUNT]>,
+    deaths: BTreeMap<Colony, [u64; DeathCause::VARIANT_COUNT]>,
+    /// Food delivered to each nest, by [`nest_label`] so the series survive a resume.
+    food_delivered: BTreeMap<String, f32>,
+    kinds_delivered: BTreeMap<Colony, [f32; FoodKind::VARIANT_COUNT]>,
+    /// Energy shared, by the kind of the ant giving it and then the kind receiving it.
+    food_shared: BTreeMap<Colony, [[f32; AntKind::VARIANT_COUNT]; AntKind::VARIANT_COUNT]>,
+}
+
+impl MetricsRecorder {
+    pub fn create(path: &Path, format: MetricsFormat) -> io::Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        if let MetricsFormat::Csv = format {
+            writeln!(writer, "tick,time,metric,colony,label,value")?;
+        }
+        Ok(Self {
+            format,
+            writer,
+            births: BTreeMap::new(),
+            deaths: BTreeMap::new(),
+            food_delivered: BTreeMap::new(),
+            kinds_delivered: BTreeMap::new(),
+            food_shared: BTreeMap::new(),
+        })
+    }
+
+    fn write(&mut self, record: MetricRecord) -> io::Result<()> {
+        match self.format {
+            MetricsFormat::Csv => writeln!(
+                self.writer,
+                "{},{},{},{},{},{}",
+                record.tick,
+                record.time,
+                record.metric,
+                record
+                    .colony
+                    .map(|colony| colony.to_string())
+                    .unwrap_or_default(),
+                record.label,
+                record.value
+            ),
+            MetricsFormat::JsonLines => {
+                serde_json::to_writer(&mut self.writer, &record)?;
+                writeln!(self.writer)
+            }
+        }
+    }
+}
+
+/// Names a nest by where it is, which unlike its entity stays the same across snapshots.
+fn nest_label(transform: &Transform) -> String {
+    format!(
+        "{:.0}_{:.0}",
+        transform.translation.x, transform.translation.y
+    )
+}
+
+pub fn collect_metric_events(
+    mut recorder: ResMut<MetricsRecorder>,
+    nests: Query<&Transform, With<Nest>>,
+    mut births: EventReader<AntBorn>,
+    mut deaths: EventReader<AntDied>,
+    mut deliveries: EventReader<FoodDelivered>,
+    mut exchanges: EventReader<FoodShared>,
+) {
+    for birth in births.read() {
+        recorder
+            .births
+            .entry(birth.colony)
+            .or_insert([0; AntKind::VARIANT_COUNT])[birth.kind.ordinal() as usize] += 1;
+    }
+    for death in deaths.read() {
+        recorder
+            .deaths
+            .entry(death.colony)
+            .or_insert([0; DeathCause::VARIANT_COUNT])[death.cause.ordinal() as usize] += 1;
+    }
+    for delivery in deliveries.read() {
+        if let Ok(transform) = nests.get(delivery.nest) {
+            *recorder
+                .food_delivered
+                .entry(nest_label(transform))
+                .or_default() += delivery.amount;
+        }
+        recorder
+            .kinds_delivered
+            .entry(delivery.colony)
+            .or_insert([0.0; FoodKind::VARIANT_COUNT])[delivery.kind.ordinal() as usize] +=
+            delivery.amount;
+    }
+    for exchange in exchanges.read() {
+        recorder
+            .food_shared
+            .entry(exchange.colony)
+            .or_insert([[0.0; AntKind::VARIANT_COUNT]; AntKind::VARIANT_COUNT])
+            [exchange.donor.ordinal() as usize][exchange.recipient.ordinal() as usize] +=
+            exchange.energy;
+    }
+}
+
+/// The food in the world and what is left to hand out.
+#[derive(SystemParam)]
+pub struct FoodStock<'w, 's> {
+    piles: Query<'w, 's, &'static Food>,
+    supply: Res<'w, FoodSupply>,
+}
+
+pub fn record_metrics(
+    mut recorder: ResMut<MetricsRecorder>,
+    simulation_config: Res<SimulationConfig>,
+    tick: Res<SimulationTick>,
+    ants: Query<(&AntKind, &Task, &Colony, &Crowding), With<Ant>>,
+    nests: Query<(&Nest, &Brood, &Colony, &Transform)>,
+    food: FoodStock,
+    tracks: Query<&Tracks>,
+) {
+    // Recorded before the tick counter advances, so count the tick being finished.
+    let tick = tick.0 + 1;
+    let time = tick as f64 / simulation_config.ticks_per_second;
+
+    let tracks = tracks.single();
+    let mut population: BTreeMap<Colony, [u64; AntKind::VARIANT_COUNT]> = tracks
+        .colonies()
+        .map(|colony| (colony, [0; AntKind::VARIANT_COUNT]))
+        .collect();
+    let mut tasks: BTreeMap<Colony, [u64; Task::VARIANT_COUNT]> = BTreeMap::new();
+    // Ants in the way summed over every ant, and ants held to half speed or less.
+    let mut crowding: BTreeMap<Colony, (u32, u32)> = BTreeMap::new();
+    for (kind, task, colony, ant_crowding) in ants.iter() {
+        population
+            .entry(*colony)
+            .or_insert([0; AntKind::VARIANT_COUNT])[kind.ordinal() as usize] += 1;
+        let (ahead, jammed) = crowding.entry(*colony).or_default();
+        *ahead += ant_crowding.ahead();
+        if ant_crowding.speed_factor(&simulation_config) <= 0.5 {
+            *jammed += 1;
+        }
+        // Queens don't take up tasks.
+        if *kind != AntKind::Queen {
+            tasks.entry(*colony).or_insert([0; Task::VARIANT_COUNT])[task.ordinal() as usize] += 1;
+        }
+    }
+
+    let mut series: Vec<(&str, Option<Colony>, String, f32)> = vec![];
+    for (&colony, population) in population.iter() {
+        let births = recorder.births.get(&colony);
+        for kind in AntKind::VARIANTS {
+            let i = kind.ordinal() as usize;
+            let name = kind.name().to_string();
+            let born = births.map_or(0, |births| births[i]);
+            series.push((
+                "population",
+                Some(colony),
+                name.clone(),
+                population[i] as f32,
+            ));
+            series.push(("births", Some(colony), name, born as f32));
+        }
+        let ants = population.iter().sum::<u64>().max(1) as f32;
+        let (ahead, jammed) = crowding.get(&colony).copied().unwrap_or_default();
+        series.push((
+            "crowding",
+            Some(colony),
+            "ants_ahead".to_string(),
+            ahead as f32 / ants,
+        ));
+        series.push((
+            "crowding",
+            Some(colony),
+            "jammed".to_string(),
+            jammed as f32,
+        ));
+        let doing = tasks.get(&colony);
+        for task in Task::VARIANTS {
+            let count = doing.map_or(0, |doing| doing[task.ordinal() as usize]);
+            series.push(("tasks", Some(colony), task.name().to_string(), count as f32));
+        }
+        let deaths = recorder.deaths.get(&colony);
+        for cause in DeathCause::VARIANTS {
+            let died = deaths.map_or(0, |deaths| deaths[cause.ordinal() as usize]);
+            series.push((
+                "deaths",
+                Some(colony),
+                cause.name().to_string(),
+                died as f32,
+            ));
+        }
+        let shared = recorder.food_shared.get(&colony);
+        for donor in AntKind::VARIANTS {
+            for recipient in AntKind::VARIANTS {
+                let energy = shared.map_or(0.0, |shared| {
+                    shared[donor.ordinal() as usize][recipient.ordinal() as usize]
+                });
+                let name = format!("{}_to_{}", donor.name(), recipient.name());
+                series.push(("food_shared", Some(colony), name, energy));
+            }
+        }
+        let total = tracks.total(colony);
+        for &pheromone in Pheromone::VARIANTS {
+            let name = pheromone.name().to_string();
+            series.push(("pheromone_mass", Some(colony), name, total[pheromone]));
+        }
+    }
+    let mut stored: BTreeMap<Colony, FoodStore> = BTreeMap::new();
+    let mut brood: BTreeMap<Colony, [usize; BroodStage::VARIANT_COUNT]> = BTreeMap::new();
+    let mut by_nest: Vec<_> = nests
+        .iter()
+        .map(|(nest, _, colony, transform)| (*colony, nest_label(transform), nest.food.total()))
+        .collect();
+    by_nest.sort_by(|a, b| a.0.cmp(&b.0).then_with(|| a.1.cmp(&b.1)));
+    for (colony, label, stored) in by_nest {
+        let delivered = recorder.food_delivered.get(&label).copied().unwrap_or(0.0);
+        series.push((
+            "nest_food_delivered",
+            Some(colony),
+            label.clone(),
+            delivered,
+        ));
+        series.push(("nest_food_stored", Some(colony), label, stored));
+    }
+    for (nest, nest_brood, colony, _) in nests.iter() {
+        let store = stored.entry(*colony).or_default();
+        for &kind in FoodKind::VARIANTS {
+            store[kind] += nest.food[kind];
+        }
+        let counts = brood
+            .entry(*colony)
+            .or_insert([0; BroodStage::VARIANT_COUNT]);
+        for &stage in BroodStage::VARIANTS {
+            counts[stage.ordinal() as usize] += nest_brood.count(stage);
+        }
+    }
+    for colony in tracks.colonies() {
+        let counts = brood.remove(&colony).unwrap_or_default();
+        for &stage in BroodStage::VARIANTS {
+            let name = stage.name().to_string();
+            let count = counts[stage.ordinal() as usize] as f32;
+            series.push(("brood", Some(colony), name, count));
+        }
+        let delivered = recorder.kinds_delivered.get(&colony);
+        let store = stored.remove(&colony).unwrap_or_default();
+        for &kind in FoodKind::VARIANTS {
+            let name = kind.name().to_string();
+            let amount = delivered.map_or(0.0, |delivered| delivered[kind.ordinal() as usize]);
+            series.push(("food_delivered", Some(colony), name.clone(), amount));
+            series.push(("food_stored", Some(colony), name, store[kind]));
+        }
+    }
+    let mut sources = [0.0; FoodKind::VARIANT_COUNT];
+    let mut remaining = [0.0; FoodKind::VARIANT_COUNT];
+    for food in food.piles.iter() {
+        sources[food.kind().ordinal() as usize] += 1.0;
+        remaining[food.kind().ordinal() as usize] += food.amount();
+    }
+    for &kind in FoodKind::VARIANTS {
+        let i = kind.ordinal() as usize;
+        let name = kind.name().to_string();
+        series.push(("food_sources", None, name.clone(), sources[i]));
+        series.push(("food_remaining", None, name, remaining[i]));
+    }
+    if simulation_config.food.policy == FoodPolicy::Finite {
+        series.push(("food_supply", None, String::new(), food.supply.remaining()));
+    }
+
+    let result = series
+        .iter()
+        .try_for_each(|(metric, colony, label, value)| {
+            recorder.write(MetricRecord {
+                tick,
+                time,
+                metric,
+                colony: colony.map(|colony| colony.0),
+                label,
+                value: *value,
+            })
+        })
+        .and_then(|_| recorder.writer.flush());
+    if let Err(err) = result {
+        error!("failed to write metrics: {err}");
+    }
+
+    recorder.births.clear();
+    recorder.deaths.clear();
+    recorder.food_delivered.clear();
+    recorder.kinds_delivered.clear();
+    recorder.food_shared.clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+    use serde_json::json;
+
+    use super::*;
+    use crate::{ant::spawn_ant, nest::spawn_nest};
+
+    /// A world with a worker, two nests of colony 0 and one of colony 1, recording to `path`.
+    fn metrics_world(path: &Path, format: MetricsFormat) -> World {
+        let mut world = World::new();
+        world.insert_resource(SimulationConfig::default());
+        world.insert_resource(SimulationTick(59));
+        world.insert_resource(FoodSupply::default());
+        world.insert_resource(MetricsRecorder::create(path, format).unwrap());
+        world.init_resource::<Events<AntBorn>>();
+        world.init_resource::<Events<AntDied>>();
+        world.init_resource::<Events<FoodDelivered>>();
+        world.init_resource::<Events<FoodShared>>();
+        world.spawn(Tracks::new(Vec2::new(100.0, 100.0), 10.0, 2));
+        world.run_system_once(
+            |mut commands: Commands, simulation_config: Res<SimulationConfig>| {
+                spawn_nest(&mut commands, -100.0, 50.0, Colony(0));
+                spawn_nest(&mut commands, 100.0, 50.0, Colony(0));
+                spawn_nest(&mut commands, 0.0, -200.0, Colony(1));
+                spawn_ant(
+                    &mut commands,
+                    &simulation_config,
+                    0.0,
+                    0.0,
+                    0.0,
+                    AntKind::Worker,
+                    Colony(0),
+                );
+            },
+        );
+        world
+    }
+
+    /// Delivers 3 units of protein to the nest at (-100, 50), records a sample, then records
+    /// another a second later with nothing delivered, and returns what was written.
+    fn record_two_samples(name: &str, format: MetricsFormat) -> String {
+        let path = std::env::temp_dir().join(format!("ant_colony_{}_{name}", std::process::id()));
+        let mut world = metrics_world(&path, format);
+        let nest = world
+            .query::<(Entity, &Transform, &Colony)>()
+            .iter(&world)
+            .find(|(_, transform, _)| transform.translation.x < 0.0)
+            .unwrap()
+            .0;
+        world.send_event(FoodDelivered {
+            nest,
+            colony: Colony(0),
+            kind: FoodKind::Protein,
+            amount: 3.0,
+        });
+        world.send_event(AntBorn {
+            kind: AntKind::Worker,
+            colony: Colony(0),
+        });
+
+        let mut schedule = Schedule::default();
+        schedule.add_systems((collect_metric_events, record_metrics).chain());
+        schedule.run(&mut world);
+        world.resource_mut::<SimulationTick>().0 = 119;
+        schedule.run(&mut world);
+
+        let written = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        written
+    }
+
+    #[test]
+    fn csv_rows_label_nests_by_position() {
+        let written = record_two_samples("metrics.csv", MetricsFormat::Csv);
+        let rows: Vec<_> = written.lines().collect();
+        assert_eq!(rows[0], "tick,time,metric,colony,label,value");
+        for row in [
+            "60,1,population,0,worker,1",
+            "60,1,births,0,worker,1",
+            "60,1,food_delivered,0,protein,3",
+            "60,1,nest_food_delivered,0,-100_50,3",
+            "60,1,nest_food_delivered,0,100_50,0",
+            "60,1,nest_food_delivered,1,0_-200,0",
+            "60,1,nest_food_stored,0,-100_50,5",
+            "120,2,births,0,worker,0",
+            "120,2,nest_food_delivered,0,-100_50,0",
+        ] {
+            assert!(rows.contains(&row), "no row {row}");
+        }
+        // Nests are written in the same order every time, by colony and then label.
+        let nests: Vec<_> = rows
+            .iter()
+            .filter(|row| row.starts_with("60,1,nest_food_delivered"))
+            .collect();
+        assert_eq!(
+            nests,
+            [
+                &"60,1,nest_food_delivered,0,-100_50,3",
+                &"60,1,nest_food_delivered,0,100_50,0",
+                &"60,1,nest_food_delivered,1,0_-200,0",
+            ]
+        );
+    }
+
+    #[test]
+    fn json_lines_hold_the_same_records() {
+        let written = record_two_samples("metrics.jsonl", MetricsFormat::JsonLines);
+        let records: Vec<serde_json::Value> = written
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        for record in [
+            json!({"tick": 60, "time": 1.0, "metric": "food_delivered", "colony": 0,
+                   "label": "protein", "value": 3.0}),
+            json!({"tick": 60, "time": 1.0, "metric": "nest_food_delivered", "colony": 0,
+                   "label": "-100_50", "value": 3.0}),
+            json!({"tick": 120, "time": 2.0, "metric": "nest_food_delivered", "colony": 0,
+                   "label": "-100_50", "value": 0.0}),
+        ] {
+            assert!(records.contains(&record), "no record {record}");
+        }
+        // World-wide series have no colony.
+        assert!(records
+            .iter()
+            .any(|record| record["metric"] == "food_sources" && record["colony"].is_null()));
+    }
+}
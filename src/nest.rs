@@ -1,31 +1,100 @@
-use std::time::Duration;
+use std::collections::HashMap;
 
 use bevy::{prelude::*, sprite::MaterialMesh2dBundle};
+use enum_ordinalize::Ordinalize;
 use rand::prelude::*;
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    ant::spawn_ant,
+    ant::{spawn_ant, AntBorn, AntKind, Satiation},
     assets::{Colors, Meshes},
-    config::{SimulationConfig, FIXED_DELTA_TIME, LAYER_NEST, NEST_RADIUS},
-    track::Tracks,
+    config::{SimulationConfig, LAYER_NEST},
+    food::{FoodKind, FoodStore},
+    queen::Flight,
+    rng::SimulationRng,
+    task::Task,
+    track::{Pheromone, Tracks},
 };
 
-#[derive(Component)]
+/// Identifies the colony an ant, nest or pheromone field belongs to.
+#[derive(Component, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Colony(pub u32);
+
+#[derive(Component, Clone, Serialize, Deserialize)]
 pub struct Nest {
-    pub food: f32,
+    pub food: FoodStore,
+}
+
+#[derive(Ordinalize, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BroodStage {
+    #[default]
+    Egg,
+    Larva,
+    Pupa,
+}
+
+impl BroodStage {
+    pub fn name(&self) -> &'static str {
+        match self {
+            BroodStage::Egg => "egg",
+            BroodStage::Larva => "larva",
+            BroodStage::Pupa => "pupa",
+        }
+    }
+}
+
+/// One egg, larva or pupa.
+#[derive(Clone, Default, Serialize, Deserialize)]
+struct Young {
+    stage: BroodStage,
+    /// Seconds spent in the current stage.
+    age: f32,
+    /// Energy eaten as a larva.
+    fed: f32,
+    /// Seconds a larva has gone without enough food.
+    hungry: f32,
 }
 
-#[derive(Component)]
-pub struct AntSpawner {
-    pub timer: Timer,
+/// The queen's laying and the young developing in a nest.
+#[derive(Component, Clone, Default, Serialize, Deserialize)]
+pub struct Brood {
+    /// Seconds since the queen last laid an egg, counted slower the hungrier she is.
+    since_laid: f32,
+    young: Vec<Young>,
 }
 
-pub fn spawn_nest(commands: &mut Commands, x: f32, y: f32) {
+impl Brood {
+    /// A brood of `count` freshly laid eggs.
+    pub fn with_eggs(count: usize) -> Self {
+        Self {
+            since_laid: 0.0,
+            young: vec![Young::default(); count],
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.young.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.young.is_empty()
+    }
+
+    pub fn count(&self, stage: BroodStage) -> usize {
+        self.young
+            .iter()
+            .filter(|young| young.stage == stage)
+            .count()
+    }
+}
+
+pub fn spawn_nest(commands: &mut Commands, x: f32, y: f32, colony: Colony) {
+    let mut food = FoodStore::default();
+    food[FoodKind::Sugar] = 5.0;
     commands.spawn((
-        Nest { food: 5.0 },
-        AntSpawner {
-            timer: Timer::from_seconds(60.0, TimerMode::Repeating),
-        },
+        Nest { food },
+        colony,
+        Brood::default(),
         SpatialBundle::from_transform(Transform::from_translation(Vec3::new(x, y, 0.0))),
     ));
 }
@@ -48,47 +117,328 @@ pub fn setup_nest_rendering(
     }
 }
 
-pub fn spawn_ants_from_nest(
+/// Lays eggs while the nest has a queen and can pay for them, feeds larvae from its stores and
+/// turns pupae into adult ants. Young queens leave on a nuptial flight as soon as they eclose,
+/// and ants doing nest work in the nest speed up how fast eggs hatch and pupae eclose.
+pub fn raise_brood(
     mut commands: Commands,
     simulation_config: Res<SimulationConfig>,
-    mut query: Query<(&mut Nest, &Transform, &mut AntSpawner)>,
+    mut rng: ResMut<SimulationRng>,
+    mut query: Query<(&mut Nest, &Transform, &mut Brood, &Colony)>,
+    ants: Query<(&AntKind, &Task, &Satiation, &Transform, &Colony), Without<Flight>>,
+    mut births: EventWriter<AntBorn>,
 ) {
-    let mut rng = rand::thread_rng();
-    for (mut nest, transform, mut spawner) in query.iter_mut() {
-        spawner
-            .timer
-            .tick(Duration::from_secs_f32(FIXED_DELTA_TIME));
-        if !spawner.timer.finished() {
-            continue;
+    let delta_time = simulation_config.fixed_delta_time();
+    let brood_config = &simulation_config.brood;
+    let food_kinds = &simulation_config.food_kinds;
+    let mut queen_satiations: HashMap<Colony, f32> = HashMap::new();
+    for (kind, _, satiation, _, colony) in ants.iter() {
+        if *kind == AntKind::Queen {
+            let best = queen_satiations.entry(*colony).or_default();
+            *best = best.max(satiation.amount());
+        }
+    }
+
+    for (mut nest, transform, mut brood, colony) in query.iter_mut() {
+        let brood = &mut *brood;
+        let nurses = ants
+            .iter()
+            .filter(|(_, task, _, ant_transform, ant_colony)| {
+                **task == Task::NestWork
+                    && *ant_colony == colony
+                    && ant_transform
+                        .translation
+                        .xy()
+                        .distance(transform.translation.xy())
+                        < simulation_config.nest_radius
+            })
+            .count();
+        let care = if brood.is_empty() {
+            0.0
+        } else {
+            (nurses as f32 * simulation_config.tasks.brood_per_nurse / brood.len() as f32).min(1.0)
+        };
+        let development_time = delta_time * (1.0 + simulation_config.tasks.nursing_speedup * care);
+        // Without a queen the nest lays no more eggs, though its brood still grows up.
+        let queen_satiation = queen_satiations.get(colony).copied().unwrap_or(0.0);
+        brood.since_laid += delta_time * queen_satiation;
+        if queen_satiation > 0.0
+            && brood.since_laid >= brood_config.egg_interval
+            && nest.food.energy(food_kinds) >= brood_config.egg_cost
+        {
+            brood.since_laid = 0.0;
+            nest.food.take_energy(brood_config.egg_cost, food_kinds);
+            brood.young.push(Young::default());
         }
-        if nest.food < 1.0 {
-            continue;
+
+        let mut eclosed = 0;
+        brood.young.retain_mut(|young| {
+            young.age += match young.stage {
+                BroodStage::Larva => delta_time,
+                BroodStage::Egg | BroodStage::Pupa => development_time,
+            };
+            match young.stage {
+                BroodStage::Egg => {
+                    if young.age >= brood_config.egg_duration {
+                        young.stage = BroodStage::Larva;
+                        young.age = 0.0;
+                    }
+                }
+                BroodStage::Larva => {
+                    let wanted = (brood_config.larva_feed_rate * delta_time)
+                        .min(brood_config.larva_food - young.fed);
+                    let ate = nest.food.take_energy(wanted, food_kinds);
+                    young.fed += ate;
+                    young.hungry = if ate + 0.000001 < wanted {
+                        young.hungry + delta_time
+                    } else {
+                        0.0
+                    };
+                    if young.hungry > brood_config.larva_starvation_time {
+                        return false;
+                    }
+                    if young.fed + 0.000001 >= brood_config.larva_food {
+                        young.stage = BroodStage::Pupa;
+                        young.age = 0.0;
+                    }
+                }
+                BroodStage::Pupa => {
+                    if young.age >= brood_config.pupa_duration {
+                        eclosed += 1;
+                        return false;
+                    }
+                }
+            }
+            true
+        });
+
+        for _ in 0..eclosed {
+            let nest_radius = simulation_config.nest_radius;
+            let x = transform.translation.x + rng.gen_range(-nest_radius..nest_radius);
+            let y = transform.translation.y + rng.gen_range(-nest_radius..nest_radius);
+            let rotation = rng.gen_range(0.0..std::f32::consts::PI * 2.0);
+            let kind = simulation_config.ant_kind_gen_config.gen_kind(&mut *rng);
+            let ant = spawn_ant(
+                &mut commands,
+                &simulation_config,
+                x,
+                y,
+                rotation,
+                kind,
+                *colony,
+            );
+            if kind == AntKind::Queen {
+                let flight = Flight::new(&simulation_config, Vec2::new(x, y), &mut *rng);
+                commands.entity(ant).insert(flight);
+            }
+            births.send(AntBorn {
+                kind,
+                colony: *colony,
+            });
         }
-        nest.food -= 1.0;
-        let x = transform.translation.x + rng.gen_range(-NEST_RADIUS..NEST_RADIUS);
-        let y = transform.translation.y + rng.gen_range(-NEST_RADIUS..NEST_RADIUS);
-        let rotation = rng.gen_range(0.0..std::f32::consts::PI * 2.0);
-        spawn_ant(
-            &mut commands,
-            &simulation_config,
-            x,
-            y,
-            rotation,
-            simulation_config.ant_kind_gen_config.gen_kind(&mut rng),
+    }
+}
+
+pub fn spoil_nest_food(simulation_config: Res<SimulationConfig>, mut nests: Query<&mut Nest>) {
+    for mut nest in nests.iter_mut() {
+        nest.food.spoil(
+            &simulation_config.food_kinds,
+            simulation_config.fixed_delta_time(),
         );
     }
 }
 
 pub fn emit_nest_pheromones(
     simulation_config: Res<SimulationConfig>,
-    mut query: Query<&Transform, With<Nest>>,
+    query: Query<(&Transform, &Colony), With<Nest>>,
     mut tracks: Query<&mut Tracks>,
 ) {
     let mut tracks = tracks.single_mut();
-    for transform in query.iter_mut() {
-        let nest_concentration = simulation_config.nest_track_concentration;
-        tracks.within_circle_mut(transform.translation.xy(), NEST_RADIUS, |track| {
-            track.nest += nest_concentration * FIXED_DELTA_TIME;
+    let cap = simulation_config.pheromones[Pheromone::Nest].cap;
+    let nest_concentration =
+        simulation_config.nest_track_concentration * simulation_config.fixed_delta_time();
+    for (transform, colony) in query.iter() {
+        tracks.within_circle_mut(
+            *colony,
+            Pheromone::Nest,
+            transform.translation.xy(),
+            simulation_config.nest_radius,
+            |value| *value = (*value + nest_concentration).min(cap),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+    use crate::config::BroodConfig;
+
+    /// A world stepping one second per run, with a single nest at the origin holding `sugar`
+    /// and `young`, and no queen to lay more.
+    fn brood_world(brood: BroodConfig, sugar: f32, young: Vec<Young>) -> (World, Entity) {
+        let mut world = World::new();
+        world.insert_resource(SimulationConfig {
+            ticks_per_second: 1.0,
+            brood,
+            ..default()
         });
+        world.insert_resource(SimulationRng::new(Some(1)));
+        world.init_resource::<Events<AntBorn>>();
+        let mut food = FoodStore::default();
+        food[FoodKind::Sugar] = sugar;
+        let nest = world
+            .spawn((
+                Nest { food },
+                Transform::default(),
+                Brood {
+                    since_laid: 0.0,
+                    young,
+                },
+                Colony(0),
+            ))
+            .id();
+        (world, nest)
+    }
+
+    fn add_worker(world: &mut World, position: Vec2, task: Task) {
+        let ant = world.run_system_once(
+            move |mut commands: Commands, simulation_config: Res<SimulationConfig>| {
+                spawn_ant(
+                    &mut commands,
+                    &simulation_config,
+                    position.x,
+                    position.y,
+                    0.0,
+                    AntKind::Worker,
+                    Colony(0),
+                )
+            },
+        );
+        *world.get_mut::<Task>(ant).unwrap() = task;
+    }
+
+    /// The number of eggs, larvae and pupae in the nest.
+    fn stages(world: &World, nest: Entity) -> [usize; 3] {
+        let brood = world.get::<Brood>(nest).unwrap();
+        [BroodStage::Egg, BroodStage::Larva, BroodStage::Pupa].map(|stage| brood.count(stage))
+    }
+
+    fn larva() -> Young {
+        Young {
+            stage: BroodStage::Larva,
+            ..default()
+        }
+    }
+
+    #[test]
+    fn an_egg_grows_into_an_adult_on_time() {
+        let (mut world, nest) = brood_world(
+            BroodConfig {
+                egg_duration: 3.0,
+                larva_food: 1.0,
+                larva_feed_rate: 0.5,
+                pupa_duration: 2.0,
+                ..default()
+            },
+            10.0,
+            vec![Young::default()],
+        );
+
+        let mut history = vec![];
+        for _ in 0..7 {
+            world.run_system_once(raise_brood);
+            history.push(stages(&world, nest));
+        }
+        assert_eq!(
+            history,
+            [
+                [1, 0, 0],
+                [1, 0, 0],
+                [0, 1, 0],
+                [0, 1, 0],
+                [0, 0, 1],
+                [0, 0, 1],
+                [0, 0, 0],
+            ]
+        );
+        // The larva ate exactly what it needed.
+        let food = &world.get::<Nest>(nest).unwrap().food;
+        assert!((food[FoodKind::Sugar] - 9.0).abs() < 1e-5);
+        assert_eq!(world.query::<&AntKind>().iter(&world).count(), 1);
+        assert_eq!(world.resource::<Events<AntBorn>>().len(), 1);
+    }
+
+    #[test]
+    fn larvae_starve_without_food() {
+        let (mut world, nest) = brood_world(
+            BroodConfig {
+                larva_starvation_time: 2.0,
+                ..default()
+            },
+            0.0,
+            vec![larva()],
+        );
+
+        for _ in 0..2 {
+            world.run_system_once(raise_brood);
+            assert_eq!(stages(&world, nest), [0, 1, 0]);
+        }
+        world.run_system_once(raise_brood);
+        assert_eq!(stages(&world, nest), [0, 0, 0]);
+        assert_eq!(world.resource::<Events<AntBorn>>().len(), 0);
+    }
+
+    #[test]
+    fn fed_larvae_stop_counting_towards_starvation() {
+        let (mut world, nest) = brood_world(
+            BroodConfig {
+                larva_food: 10.0,
+                larva_feed_rate: 1.0,
+                larva_starvation_time: 2.0,
+                ..default()
+            },
+            0.0,
+            vec![larva()],
+        );
+
+        for _ in 0..2 {
+            world.run_system_once(raise_brood);
+        }
+        world.get_mut::<Nest>(nest).unwrap().food[FoodKind::Sugar] = 1.0;
+        world.run_system_once(raise_brood);
+        for _ in 0..2 {
+            world.run_system_once(raise_brood);
+            assert_eq!(stages(&world, nest), [0, 1, 0]);
+        }
+        world.run_system_once(raise_brood);
+        assert_eq!(stages(&world, nest), [0, 0, 0]);
+    }
+
+    #[test]
+    fn nurses_in_the_nest_speed_up_development() {
+        let brood = BroodConfig {
+            egg_duration: 4.0,
+            ..default()
+        };
+        let (mut nursed, nursed_nest) = brood_world(brood.clone(), 10.0, vec![Young::default()]);
+        add_worker(&mut nursed, Vec2::ZERO, Task::NestWork);
+        // Away from the nest, or not doing nest work, an ant doesn't help.
+        let (mut unnursed, unnursed_nest) = brood_world(brood, 10.0, vec![Young::default()]);
+        add_worker(&mut unnursed, Vec2::new(500.0, 0.0), Task::NestWork);
+        add_worker(&mut unnursed, Vec2::ZERO, Task::Forage);
+
+        for _ in 0..2 {
+            nursed.run_system_once(raise_brood);
+            unnursed.run_system_once(raise_brood);
+        }
+        // One nurse is plenty for one egg, which then develops twice as fast by default.
+        assert_eq!(stages(&nursed, nursed_nest), [0, 1, 0]);
+        assert_eq!(stages(&unnursed, unnursed_nest), [1, 0, 0]);
+        for _ in 0..2 {
+            unnursed.run_system_once(raise_brood);
+        }
+        assert_eq!(stages(&unnursed, unnursed_nest), [0, 1, 0]);
     }
 }
@@ -0,0 +1,259 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::config::SimulationConfig;
+
+/// A solid shape that ants can't walk, sense or lay pheromone through.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(tag = "shape", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Obstacle {
+    Rectangle {
+        center: [f32; 2],
+        size: [f32; 2],
+    },
+    Circle {
+        center: [f32; 2],
+        radius: f32,
+    },
+    /// A straight wall of some thickness between two points.
+    Wall {
+        from: [f32; 2],
+        to: [f32; 2],
+        thickness: f32,
+    },
+}
+
+impl Obstacle {
+    pub fn contains(&self, point: Vec2) -> bool {
+        match *self {
+            Obstacle::Rectangle { center, size } => {
+                let offset = (point - Vec2::from(center)).abs();
+                offset.x <= size[0] / 2.0 && offset.y <= size[1] / 2.0
+            }
+            Obstacle::Circle { center, radius } => point.distance(Vec2::from(center)) <= radius,
+            Obstacle::Wall {
+                from,
+                to,
+                thickness,
+            } => {
+                let (from, to) = (Vec2::from(from), Vec2::from(to));
+                let along = to - from;
+                let t = if along == Vec2::ZERO {
+                    0.0
+                } else {
+                    ((point - from).dot(along) / along.length_squared()).clamp(0.0, 1.0)
+                };
+                point.distance(from + along * t) <= thickness / 2.0
+            }
+        }
+    }
+
+    pub fn is_valid(&self) -> bool {
+        match *self {
+            Obstacle::Rectangle { size, .. } => size[0] > 0.0 && size[1] > 0.0,
+            Obstacle::Circle { radius, .. } => radius > 0.0,
+            Obstacle::Wall { thickness, .. } => thickness > 0.0,
+        }
+    }
+}
+
+/// The configured obstacles rasterised onto the same grid as the pheromone fields.
+#[derive(Resource, Clone)]
+pub struct Obstacles {
+    blocked: Vec<bool>,
+    world_size: Vec2,
+    resolution: f32,
+    width: usize,
+    height: usize,
+}
+
+impl Obstacles {
+    pub fn new(world_size: Vec2, resolution: f32) -> Self {
+        let width = (world_size.x / resolution) as usize;
+        let height = (world_size.y / resolution) as usize;
+        Self {
+            blocked: vec![false; width * height],
+            world_size,
+            resolution,
+            width,
+            height,
+        }
+    }
+
+    pub fn from_config(simulation_config: &SimulationConfig) -> Self {
+        let mut obstacles = Self::new(
+            Vec2::new(
+                simulation_config.world_width,
+                simulation_config.world_height,
+            ),
+            simulation_config.track_resolution,
+        );
+        for y in 0..obstacles.height {
+            for x in 0..obstacles.width {
+                let center = obstacles.cell_center(x, y);
+                obstacles.blocked[x + y * obstacles.width] = simulation_config
+                    .obstacles
+                    .iter()
+                    .any(|obstacle| obstacle.contains(center));
+            }
+        }
+        obstacles
+    }
+
+    /// One flag per cell, laid out like the planes of [`Tracks`](crate::track::Tracks).
+    pub fn blocked_cells(&self) -> &[bool] {
+        &self.blocked
+    }
+
+    pub fn set_blocked(&mut self, x: usize, y: usize, blocked: bool) {
+        self.blocked[x + y * self.width] = blocked;
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    fn cell_center(&self, x: usize, y: usize) -> Vec2 {
+        Vec2::new(
+            (x as f32 + 0.5) * self.resolution - self.world_size.x / 2.0,
+            self.world_size.y / 2.0 - (y as f32 + 0.5) * self.resolution,
+        )
+    }
+
+    /// Whether `position` lies in a blocked cell; outside the world nothing is blocked.
+    pub fn is_blocked(&self, position: Vec2) -> bool {
+        let x = (position.x + self.world_size.x / 2.0) / self.resolution;
+        let y = (-position.y + self.world_size.y / 2.0) / self.resolution;
+        if x < 0.0 || y < 0.0 || x >= self.width as f32 || y >= self.height as f32 {
+            return false;
+        }
+        self.blocked[x as usize + y as usize * self.width]
+    }
+
+    /// Whether the straight line between two points stays out of blocked cells.
+    pub fn line_clear(&self, from: Vec2, to: Vec2) -> bool {
+        let steps = (from.distance(to) / (self.resolution / 2.0))
+            .ceil()
+            .max(1.0) as usize;
+        (0..=steps).all(|step| !self.is_blocked(from.lerp(to, step as f32 / steps as f32)))
+    }
+
+    /// Roughly how much of the circle is blocked, from 0 to 1, sampled at its center and eight
+    /// points around its edge.
+    pub fn blocked_fraction(&self, center: Vec2, radius: f32) -> f32 {
+        let samples =
+            std::iter::once(center).chain((0..8).map(|i| {
+                center + Vec2::from_angle(i as f32 * std::f32::consts::FRAC_PI_4) * radius
+            }));
+        samples.filter(|sample| self.is_blocked(*sample)).count() as f32 / 9.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+    use crate::{
+        ant::{spawn_ant, walk_ants, AntKind},
+        nest::Colony,
+    };
+
+    /// A 200 by 200 world whose right half from x = 0 on is walled off.
+    fn walled_config() -> SimulationConfig {
+        SimulationConfig {
+            world_width: 200.0,
+            world_height: 200.0,
+            ticks_per_second: 1.0,
+            obstacles: vec![Obstacle::Rectangle {
+                center: [50.0, 0.0],
+                size: [100.0, 200.0],
+            }],
+            ..default()
+        }
+    }
+
+    #[test]
+    fn shapes_contain_the_points_inside_them() {
+        let rectangle = Obstacle::Rectangle {
+            center: [10.0, 0.0],
+            size: [4.0, 2.0],
+        };
+        assert!(rectangle.contains(Vec2::new(11.9, 0.9)));
+        assert!(!rectangle.contains(Vec2::new(12.1, 0.0)));
+        assert!(!rectangle.contains(Vec2::new(10.0, 1.1)));
+
+        let circle = Obstacle::Circle {
+            center: [0.0, 0.0],
+            radius: 5.0,
+        };
+        assert!(circle.contains(Vec2::new(3.0, 4.0)));
+        assert!(!circle.contains(Vec2::new(3.1, 4.0)));
+
+        // Walls are rounded off past their ends.
+        let wall = Obstacle::Wall {
+            from: [0.0, 0.0],
+            to: [10.0, 0.0],
+            thickness: 2.0,
+        };
+        assert!(wall.contains(Vec2::new(5.0, 1.0)));
+        assert!(!wall.contains(Vec2::new(5.0, 1.1)));
+        assert!(wall.contains(Vec2::new(10.6, 0.6)));
+        assert!(!wall.contains(Vec2::new(11.1, 0.0)));
+    }
+
+    #[test]
+    fn lines_are_clear_only_on_open_ground() {
+        let obstacles = Obstacles::from_config(&walled_config());
+        assert!(obstacles.line_clear(Vec2::new(-90.0, -50.0), Vec2::new(-10.0, 50.0)));
+        assert!(!obstacles.line_clear(Vec2::new(-10.0, 0.0), Vec2::new(10.0, 0.0)));
+        // Past the world's edge nothing is blocked.
+        assert!(!obstacles.is_blocked(Vec2::new(150.0, 0.0)));
+    }
+
+    #[test]
+    fn blocked_fraction_counts_the_samples_in_walls() {
+        let obstacles = Obstacles::from_config(&walled_config());
+        assert_eq!(obstacles.blocked_fraction(Vec2::new(-50.0, 0.0), 5.0), 0.0);
+        assert_eq!(obstacles.blocked_fraction(Vec2::new(50.0, 0.0), 5.0), 1.0);
+        // The center and the five samples on the right of it.
+        assert_eq!(
+            obstacles.blocked_fraction(Vec2::new(2.0, 0.0), 5.0),
+            6.0 / 9.0
+        );
+    }
+
+    #[test]
+    fn ants_slide_along_walls_instead_of_passing_through() {
+        let simulation_config = walled_config();
+        let mut world = World::new();
+        world.insert_resource(Obstacles::from_config(&simulation_config));
+        world.insert_resource(simulation_config);
+        // Heading up and to the right, straight at the wall.
+        let ant = world.run_system_once(
+            |mut commands: Commands, simulation_config: Res<SimulationConfig>| {
+                spawn_ant(
+                    &mut commands,
+                    &simulation_config,
+                    -2.0,
+                    0.0,
+                    -std::f32::consts::FRAC_PI_4,
+                    AntKind::Worker,
+                    Colony(0),
+                )
+            },
+        );
+
+        world.run_system_once(walk_ants);
+        let position = world.get::<Transform>(ant).unwrap().translation.xy();
+        let step = world.resource::<SimulationConfig>().ant_speed;
+        assert!(
+            position.abs_diff_eq(Vec2::new(-2.0, step / 2.0_f32.sqrt()), 1e-4),
+            "{position}"
+        );
+    }
+}
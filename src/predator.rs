@@ -0,0 +1,380 @@
+use std::collections::HashSet;
+
+use bevy::{ecs::system::SystemParam, prelude::*, sprite::MaterialMesh2dBundle};
+use enum_ordinalize::Ordinalize;
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ant::{Ant, AntDied, DeathCause},
+    assets::{Colors, Meshes},
+    combat::{Armour, Health},
+    config::{SimulationConfig, LAYER_PREDATOR},
+    food::random_open_position,
+    nest::{Colony, Nest},
+    obstacle::Obstacles,
+    queen::Flight,
+    rng::SimulationRng,
+    spatial::SpatialIndex,
+};
+
+#[derive(
+    Component, Ordinalize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum PredatorKind {
+    /// Roams the world and runs down ants it spots.
+    Spider,
+    /// Sits in a pit and catches ants that stumble in.
+    Antlion,
+}
+
+impl PredatorKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            PredatorKind::Spider => "spider",
+            PredatorKind::Antlion => "antlion",
+        }
+    }
+}
+
+#[derive(Component, Clone, Default, Serialize, Deserialize)]
+pub struct Predator {
+    /// Seconds left eating the last catch, during which it doesn't hunt.
+    digesting: f32,
+}
+
+pub fn spawn_predator(
+    commands: &mut Commands,
+    simulation_config: &SimulationConfig,
+    x: f32,
+    y: f32,
+    rotation: f32,
+    kind: PredatorKind,
+) -> Entity {
+    let predator_config = &simulation_config.predators[kind];
+    commands
+        .spawn((
+            Predator::default(),
+            kind,
+            Health::new(predator_config.health),
+            Armour(predator_config.armour),
+            SpatialBundle::from_transform(
+                Transform::from_translation(Vec3::new(x, y, 0.0))
+                    .with_rotation(Quat::from_rotation_z(rotation)),
+            ),
+        ))
+        .id()
+}
+
+/// Places every configured predator at a random open spot, skipping any that find none.
+pub fn spawn_random_predators(
+    commands: &mut Commands,
+    simulation_config: &SimulationConfig,
+    obstacles: &Obstacles,
+    rng: &mut impl Rng,
+) {
+    for &kind in PredatorKind::VARIANTS {
+        for _ in 0..simulation_config.predators[kind].count {
+            let Some(position) = random_open_position(simulation_config, obstacles, rng) else {
+                continue;
+            };
+            let rotation = rng.gen_range(0.0..std::f32::consts::TAU);
+            spawn_predator(
+                commands,
+                simulation_config,
+                position.x,
+                position.y,
+                rotation,
+                kind,
+            );
+        }
+    }
+}
+
+pub fn setup_predator_rendering(
+    mut commands: Commands,
+    simulation_config: Res<SimulationConfig>,
+    meshes: Res<Meshes>,
+    colors: Res<Colors>,
+    predators: Query<(Entity, &PredatorKind), Added<Predator>>,
+) {
+    for (entity, kind) in predators.iter() {
+        let radius = simulation_config.predators[*kind].capture_radius;
+        commands.entity(entity).with_children(|parent| {
+            parent.spawn(MaterialMesh2dBundle {
+                mesh: meshes.predator.clone(),
+                material: colors.predator(*kind),
+                transform: Transform::from_translation(Vec3::Z * LAYER_PREDATOR)
+                    .with_scale(Vec3::splat(radius)),
+                ..Default::default()
+            });
+        });
+    }
+}
+
+/// Ants on a nuptial flight are out of reach.
+type PreyFilter = (With<Ant>, Without<Flight>);
+
+/// The ants predators can go after: any not on a nuptial flight or sheltering in a nest.
+#[derive(SystemParam)]
+pub struct Prey<'w, 's> {
+    ant_index: Res<'w, SpatialIndex<Ant>>,
+    nest_index: Res<'w, SpatialIndex<Nest>>,
+    ants: Query<'w, 's, (&'static Transform, &'static Colony), PreyFilter>,
+}
+
+impl Prey<'_, '_> {
+    /// The closest ant within `radius` of `position` in plain sight and not already caught.
+    fn nearest(
+        &self,
+        position: Vec2,
+        radius: f32,
+        obstacles: &Obstacles,
+        caught: &HashSet<Entity>,
+    ) -> Option<(Entity, Vec2, Colony)> {
+        let prey = self.ant_index.nearest_within(position, radius, |entity| {
+            self.ants.get(entity).is_ok_and(|(transform, _)| {
+                let ant_position = transform.translation.xy();
+                !caught.contains(&entity)
+                    && self.nest_index.within(ant_position, 0.0).next().is_none()
+                    && obstacles.line_clear(position, ant_position)
+            })
+        })?;
+        let (transform, colony) = self.ants.get(prey).unwrap();
+        Some((prey, transform.translation.xy(), *colony))
+    }
+}
+
+/// Predators go after the nearest ant they can see, wandering while there is none, and catch
+/// ants that come within reach. Caught ants are despawned at once, like ants killed in a
+/// fight, so they can't act again this tick.
+pub fn hunt(
+    mut commands: Commands,
+    simulation_config: Res<SimulationConfig>,
+    obstacles: Res<Obstacles>,
+    mut rng: ResMut<SimulationRng>,
+    prey: Prey,
+    mut predators: Query<(&mut Transform, &mut Predator, &PredatorKind), Without<Ant>>,
+    mut deaths: EventWriter<AntDied>,
+) {
+    let delta_time = simulation_config.fixed_delta_time();
+    let bounds = Vec2::new(
+        simulation_config.world_width / 2.0,
+        simulation_config.world_height / 2.0,
+    );
+    // Despawning waits for the end of the system, so don't catch the same ant twice.
+    let mut caught = HashSet::new();
+
+    for (mut transform, mut predator, kind) in predators.iter_mut() {
+        let predator_config = &simulation_config.predators[*kind];
+        if predator.digesting > 0.0 {
+            predator.digesting = (predator.digesting - delta_time).max(0.0);
+            continue;
+        }
+
+        let position = transform.translation.xy();
+        let target = prey.nearest(
+            position,
+            predator_config.detection_radius,
+            &obstacles,
+            &caught,
+        );
+
+        if let Some((entity, ant_position, colony)) = target {
+            if ant_position.distance(position) < predator_config.capture_radius {
+                deaths.send(AntDied {
+                    entity,
+                    colony,
+                    cause: DeathCause::Predation,
+                });
+                commands.entity(entity).despawn_recursive();
+                caught.insert(entity);
+                predator.digesting = predator_config.handling_time;
+                continue;
+            }
+        }
+
+        if predator_config.speed <= 0.0 {
+            continue;
+        }
+        let forward = transform.up().xy();
+        let direction = match target {
+            Some((_, ant_position, _)) => (ant_position - position).normalize_or(forward),
+            None => {
+                let turn = rng.gen_range(-std::f32::consts::FRAC_PI_8..std::f32::consts::FRAC_PI_8);
+                Vec2::from_angle(turn).rotate(forward)
+            }
+        };
+        let next = position + direction * predator_config.speed * delta_time;
+        // Turn back from walls and the world's edges.
+        if obstacles.is_blocked(next) || next.abs().cmpge(bounds).any() {
+            transform.rotation = Quat::from_rotation_arc_2d(Vec2::Y, -direction);
+            continue;
+        }
+        transform.translation = next.extend(transform.translation.z);
+        transform.rotation = Quat::from_rotation_arc_2d(Vec2::Y, direction);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+    use crate::{
+        ant::{spawn_ant, AntKind},
+        nest::spawn_nest,
+        obstacle::Obstacle,
+        spatial::update_spatial_index,
+    };
+
+    /// A world stepping one second per run with `obstacles` in it, and a schedule that hunts
+    /// once per run.
+    fn hunting_world(obstacles: Vec<Obstacle>) -> (World, Schedule) {
+        let mut world = World::new();
+        let simulation_config = SimulationConfig {
+            ticks_per_second: 1.0,
+            obstacles,
+            ..default()
+        };
+        world.insert_resource(SpatialIndex::<Ant>::from_config(&simulation_config));
+        world.insert_resource(SpatialIndex::<Nest>::from_config(&simulation_config));
+        world.insert_resource(Obstacles::from_config(&simulation_config));
+        world.insert_resource(simulation_config);
+        world.insert_resource(SimulationRng::new(Some(1)));
+        world.init_resource::<Events<AntDied>>();
+        let mut schedule = Schedule::default();
+        schedule.add_systems(
+            (
+                update_spatial_index::<Ant>,
+                update_spatial_index::<Nest>,
+                hunt,
+            )
+                .chain(),
+        );
+        (world, schedule)
+    }
+
+    fn add_predator(world: &mut World, position: Vec2, kind: PredatorKind) -> Entity {
+        world.run_system_once(
+            move |mut commands: Commands, simulation_config: Res<SimulationConfig>| {
+                spawn_predator(
+                    &mut commands,
+                    &simulation_config,
+                    position.x,
+                    position.y,
+                    0.0,
+                    kind,
+                )
+            },
+        )
+    }
+
+    fn add_ant(world: &mut World, position: Vec2) -> Entity {
+        world.run_system_once(
+            move |mut commands: Commands, simulation_config: Res<SimulationConfig>| {
+                spawn_ant(
+                    &mut commands,
+                    &simulation_config,
+                    position.x,
+                    position.y,
+                    0.0,
+                    AntKind::Worker,
+                    Colony(0),
+                )
+            },
+        )
+    }
+
+    /// The ants eaten since the last call.
+    fn eaten(world: &mut World) -> Vec<Entity> {
+        world
+            .resource_mut::<Events<AntDied>>()
+            .drain()
+            .map(|death| {
+                assert_eq!(death.cause.name(), DeathCause::Predation.name());
+                death.entity
+            })
+            .collect()
+    }
+
+    #[test]
+    fn predators_digest_a_catch_before_hunting_again() {
+        let (mut world, mut schedule) = hunting_world(vec![]);
+        let antlion = add_predator(&mut world, Vec2::ZERO, PredatorKind::Antlion);
+        let first = add_ant(&mut world, Vec2::new(3.0, 0.0));
+
+        schedule.run(&mut world);
+        assert_eq!(eaten(&mut world), [first]);
+        assert!(world.get_entity(first).is_none());
+        let handling_time =
+            world.resource::<SimulationConfig>().predators[PredatorKind::Antlion].handling_time;
+        assert_eq!(
+            world.get::<Predator>(antlion).unwrap().digesting,
+            handling_time
+        );
+
+        let second = add_ant(&mut world, Vec2::new(3.0, 0.0));
+        for _ in 0..handling_time as usize {
+            schedule.run(&mut world);
+        }
+        assert!(eaten(&mut world).is_empty());
+        schedule.run(&mut world);
+        assert_eq!(eaten(&mut world), [second]);
+    }
+
+    #[test]
+    fn ants_in_a_nest_are_out_of_reach() {
+        let (mut world, mut schedule) = hunting_world(vec![]);
+        world.run_system_once(|mut commands: Commands| {
+            spawn_nest(&mut commands, 0.0, 0.0, Colony(0));
+        });
+        add_predator(&mut world, Vec2::new(5.0, 0.0), PredatorKind::Antlion);
+        let ant = add_ant(&mut world, Vec2::ZERO);
+
+        for _ in 0..3 {
+            schedule.run(&mut world);
+        }
+        assert!(eaten(&mut world).is_empty());
+        assert!(world.get_entity(ant).is_some());
+    }
+
+    #[test]
+    fn spiders_only_chase_ants_they_can_see() {
+        let wall = Obstacle::Rectangle {
+            center: [-10.0, 0.0],
+            size: [10.0, 40.0],
+        };
+        let mut positions = vec![];
+        for obstacles in [vec![], vec![wall]] {
+            let (mut world, mut schedule) = hunting_world(obstacles);
+            let spider = add_predator(&mut world, Vec2::new(-30.0, 0.0), PredatorKind::Spider);
+            add_ant(&mut world, Vec2::new(5.0, 0.0));
+            schedule.run(&mut world);
+            positions.push(world.get::<Transform>(spider).unwrap().translation.xy());
+        }
+        // In plain sight the spider heads straight for the ant; behind the wall it wanders on
+        // the way it was facing.
+        assert!(positions[0].abs_diff_eq(Vec2::new(-22.0, 0.0), 1e-4));
+        assert!(positions[1].y > 7.0 && (positions[1].x + 30.0).abs() < 3.1);
+    }
+
+    #[test]
+    fn an_ant_is_only_caught_once() {
+        let (mut world, mut schedule) = hunting_world(vec![]);
+        let antlions = [
+            add_predator(&mut world, Vec2::new(-3.0, 0.0), PredatorKind::Antlion),
+            add_predator(&mut world, Vec2::new(3.0, 0.0), PredatorKind::Antlion),
+        ];
+        let ant = add_ant(&mut world, Vec2::ZERO);
+
+        schedule.run(&mut world);
+        assert_eq!(eaten(&mut world), [ant]);
+        let digesting = antlions
+            .iter()
+            .filter(|antlion| world.get::<Predator>(**antlion).unwrap().digesting > 0.0)
+            .count();
+        assert_eq!(digesting, 1);
+    }
+}
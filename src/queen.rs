@@ -0,0 +1,215 @@
+use bevy::prelude::*;
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ant::{AntDied, DeathCause},
+    config::SimulationConfig,
+    nest::{spawn_nest, Colony, Nest},
+    obstacle::Obstacles,
+    track::Tracks,
+};
+
+/// A young queen on her nuptial flight, heading for the spot where she will try to found a
+/// colony of her own.
+#[derive(Component, Clone, Serialize, Deserialize)]
+pub struct Flight {
+    target: Vec2,
+}
+
+impl Flight {
+    /// A flight from `origin` in a random direction, far enough to clear the home nest.
+    pub fn new(simulation_config: &SimulationConfig, origin: Vec2, rng: &mut impl Rng) -> Self {
+        let queen_config = &simulation_config.queen;
+        let angle = rng.gen_range(0.0..std::f32::consts::TAU);
+        let distance = if queen_config.min_nest_distance < queen_config.flight_distance {
+            rng.gen_range(queen_config.min_nest_distance..queen_config.flight_distance)
+        } else {
+            queen_config.flight_distance
+        };
+        let bounds = Vec2::new(
+            simulation_config.world_width / 2.0,
+            simulation_config.world_height / 2.0,
+        ) - Vec2::splat(simulation_config.nest_radius);
+        Self {
+            target: (origin + Vec2::from_angle(angle) * distance).clamp(-bounds, bounds),
+        }
+    }
+}
+
+/// Flies queens over everything towards their targets. On landing a queen founds a new colony,
+/// unless the spot is inside an obstacle or too close to another nest, in which case she dies.
+pub fn fly_queens(
+    mut commands: Commands,
+    simulation_config: Res<SimulationConfig>,
+    obstacles: Res<Obstacles>,
+    mut tracks: Query<&mut Tracks>,
+    nests: Query<&Transform, With<Nest>>,
+    mut queens: Query<(Entity, &mut Transform, &Flight, &mut Colony), Without<Nest>>,
+    mut deaths: EventWriter<AntDied>,
+) {
+    let mut tracks = tracks.single_mut();
+    let step = simulation_config.queen.flight_speed * simulation_config.fixed_delta_time();
+    // Nests spawned this tick aren't in the query yet.
+    let mut nest_positions: Vec<Vec2> = nests
+        .iter()
+        .map(|transform| transform.translation.xy())
+        .collect();
+
+    for (entity, mut transform, flight, mut colony) in queens.iter_mut() {
+        let position = transform.translation.xy();
+        let to_target = flight.target - position;
+        if to_target.length() > step {
+            let direction = to_target.normalize();
+            transform.translation += (direction * step).extend(0.0);
+            transform.rotation = Quat::from_rotation_arc_2d(Vec2::Y, direction);
+            continue;
+        }
+
+        transform.translation = flight.target.extend(transform.translation.z);
+        let crowded = nest_positions
+            .iter()
+            .any(|nest| nest.distance(flight.target) < simulation_config.queen.min_nest_distance);
+        if crowded || obstacles.is_blocked(flight.target) {
+            deaths.send(AntDied {
+                entity,
+                colony: *colony,
+                cause: DeathCause::FailedFounding,
+            });
+            continue;
+        }
+        *colony = tracks.add_colony();
+        spawn_nest(&mut commands, flight.target.x, flight.target.y, *colony);
+        nest_positions.push(flight.target);
+        commands.entity(entity).remove::<Flight>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+    use crate::{
+        ant::{spawn_ant, AntBorn, AntKind},
+        config::BroodConfig,
+        nest::{raise_brood, Brood, BroodStage},
+        obstacle::Obstacle,
+        rng::SimulationRng,
+    };
+
+    /// A world stepping one second per run with a wall around (0, 300) and the nest of colony 0
+    /// at the origin.
+    fn colony_world(brood: BroodConfig) -> World {
+        let mut world = World::new();
+        let simulation_config = SimulationConfig {
+            ticks_per_second: 1.0,
+            obstacles: vec![Obstacle::Circle {
+                center: [0.0, 300.0],
+                radius: 40.0,
+            }],
+            brood,
+            ..default()
+        };
+        world.insert_resource(Obstacles::from_config(&simulation_config));
+        world.spawn(Tracks::new(
+            Vec2::new(
+                simulation_config.world_width,
+                simulation_config.world_height,
+            ),
+            simulation_config.track_resolution,
+            1,
+        ));
+        world.insert_resource(simulation_config);
+        world.insert_resource(SimulationRng::new(Some(1)));
+        world.init_resource::<Events<AntDied>>();
+        world.init_resource::<Events<AntBorn>>();
+        world.run_system_once(|mut commands: Commands| {
+            spawn_nest(&mut commands, 0.0, 0.0, Colony(0));
+        });
+        world
+    }
+
+    /// A queen of colony 0 about to land on `target`.
+    fn add_queen(world: &mut World, target: Vec2) -> Entity {
+        world
+            .spawn((
+                Transform::from_translation((target - Vec2::X).extend(0.0)),
+                Flight { target },
+                Colony(0),
+            ))
+            .id()
+    }
+
+    #[test]
+    fn queens_found_colonies_only_on_open_ground_away_from_nests() {
+        let mut world = colony_world(default());
+        let founder = add_queen(&mut world, Vec2::new(400.0, 0.0));
+        let too_close = add_queen(&mut world, Vec2::new(100.0, 0.0));
+        let walled_in = add_queen(&mut world, Vec2::new(0.0, 300.0));
+
+        world.run_system_once(fly_queens);
+
+        assert_eq!(
+            world.query::<&Tracks>().single(&world).colonies().count(),
+            2
+        );
+        assert_eq!(world.get::<Colony>(founder).unwrap().0, 1);
+        assert!(world.get::<Flight>(founder).is_none());
+        let mut nests: Vec<_> = world
+            .query_filtered::<(&Transform, &Colony), With<Nest>>()
+            .iter(&world)
+            .map(|(transform, colony)| (transform.translation.xy(), *colony))
+            .collect();
+        nests.sort_by_key(|(_, colony)| *colony);
+        assert!(nests == [(Vec2::ZERO, Colony(0)), (Vec2::new(400.0, 0.0), Colony(1))]);
+
+        let mut failed: Vec<_> = world
+            .resource_mut::<Events<AntDied>>()
+            .drain()
+            .map(|death| {
+                assert_eq!(death.cause.name(), DeathCause::FailedFounding.name());
+                death.entity
+            })
+            .collect();
+        failed.sort();
+        let mut expected = [too_close, walled_in];
+        expected.sort();
+        assert_eq!(failed, expected);
+    }
+
+    #[test]
+    fn a_nest_without_a_queen_lays_no_more_eggs() {
+        let mut world = colony_world(BroodConfig {
+            egg_interval: 1.0,
+            egg_cost: 0.0,
+            egg_duration: 100.0,
+            ..default()
+        });
+        let queen = world.run_system_once(
+            |mut commands: Commands, simulation_config: Res<SimulationConfig>| {
+                spawn_ant(
+                    &mut commands,
+                    &simulation_config,
+                    0.0,
+                    0.0,
+                    0.0,
+                    AntKind::Queen,
+                    Colony(0),
+                )
+            },
+        );
+        let eggs = |world: &mut World| world.query::<&Brood>().single(world).count(BroodStage::Egg);
+
+        for _ in 0..3 {
+            world.run_system_once(raise_brood);
+        }
+        assert_eq!(eggs(&mut world), 3);
+
+        world.despawn(queen);
+        for _ in 0..3 {
+            world.run_system_once(raise_brood);
+        }
+        assert_eq!(eggs(&mut world), 3);
+    }
+}
@@ -0,0 +1,45 @@
+use bevy::prelude::*;
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+/// The single source of randomness for the simulation, so that a seed and a config fully
+/// determine a run.
+#[derive(Resource, Clone, Serialize, Deserialize)]
+pub struct SimulationRng {
+    seed: u64,
+    rng: ChaCha8Rng,
+}
+
+impl SimulationRng {
+    pub fn new(seed: Option<u64>) -> Self {
+        // Seeds are replayed through TOML, whose integers are signed.
+        let seed = seed.unwrap_or_else(|| rand::thread_rng().gen_range(0..=i64::MAX as u64));
+        Self {
+            seed,
+            rng: ChaCha8Rng::seed_from_u64(seed),
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+}
+
+impl RngCore for SimulationRng {
+    fn next_u32(&mut self) -> u32 {
+        self.rng.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.rng.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.rng.fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.rng.try_fill_bytes(dest)
+    }
+}
@@ -0,0 +1,85 @@
+use std::time::{Duration, Instant};
+
+use bevy::{app::AppExit, prelude::*};
+
+use crate::{
+    ant::{Ant, HeldFood, Satiation},
+    nest::Nest,
+};
+
+#[derive(Resource, Default)]
+pub struct SimulationTick(pub u64);
+
+/// The phases of a simulation tick, in order.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TickSet {
+    Simulate,
+    Record,
+    Advance,
+}
+
+#[derive(Resource)]
+pub struct RunLimits {
+    pub max_ticks: Option<u64>,
+    pub max_duration: Option<Duration>,
+    pub started: Instant,
+}
+
+impl Default for RunLimits {
+    fn default() -> Self {
+        Self {
+            max_ticks: None,
+            max_duration: None,
+            started: Instant::now(),
+        }
+    }
+}
+
+impl RunLimits {
+    fn reached(&self, ticks: u64, elapsed: Duration) -> bool {
+        self.max_ticks.is_some_and(|max_ticks| ticks >= max_ticks)
+            || self
+                .max_duration
+                .is_some_and(|max_duration| elapsed >= max_duration)
+    }
+}
+
+pub fn advance_tick(mut tick: ResMut<SimulationTick>) {
+    tick.0 += 1;
+}
+
+/// Run condition that is true on the last tick of every `interval` simulation ticks.
+pub fn on_tick_interval(interval: u64) -> impl FnMut(Res<SimulationTick>) -> bool + Clone {
+    move |tick: Res<SimulationTick>| (tick.0 + 1).is_multiple_of(interval)
+}
+
+pub fn check_run_limits(
+    limits: Res<RunLimits>,
+    tick: Res<SimulationTick>,
+    ants: Query<(&HeldFood, &Satiation), With<Ant>>,
+    nests: Query<&Nest>,
+    mut exit: EventWriter<AppExit>,
+) {
+    // Wall-clock time, since headless runs step `Time` by exact ticks rather than real time.
+    let elapsed = limits.started.elapsed();
+    if !limits.reached(tick.0, elapsed) {
+        return;
+    }
+
+    let ant_count = ants.iter().count();
+    let total_held_food: f32 = ants.iter().map(|(held_food, _)| held_food.amount()).sum();
+    let total_nest_food: f32 = nests.iter().map(|nest| nest.food.total()).sum();
+
+    info!("run finished");
+    info!("ticks: {}", tick.0);
+    info!("elapsed: {:.2}s", elapsed.as_secs_f64());
+    info!(
+        "ticks per second: {:.1}",
+        tick.0 as f64 / elapsed.as_secs_f64().max(f64::EPSILON)
+    );
+    info!("ants: {ant_count}");
+    info!("total held food: {total_held_food}");
+    info!("total nest food: {total_nest_food}");
+
+    exit.send(AppExit::Success);
+}
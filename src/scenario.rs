@@ -0,0 +1,306 @@
+use std::{fs, path::Path};
+
+use bevy::prelude::*;
+use image::{Rgba, RgbaImage};
+use serde::Serialize;
+
+use crate::{
+    config::{ConfigError, ConfigLayer, SimulationConfig},
+    food::{FoodKind, FoodPile},
+    obstacle::Obstacle,
+};
+
+/// Food placed in a map cell at full intensity.
+pub const SCENARIO_MAX_FOOD_PER_CELL: f32 = 250.0;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Cell {
+    Empty,
+    Wall,
+    Nest,
+    /// Food with an intensity from 0 to 1.
+    Food(f32),
+}
+
+/// A world layout drawn as a grid of cells, which is stretched over the whole world.
+pub struct Scenario {
+    cells: Vec<Cell>,
+    width: usize,
+    height: usize,
+}
+
+/// The config keys a scenario sets. Plain values come before arrays of tables, which TOML
+/// requires.
+#[derive(Serialize)]
+struct Layout {
+    random_food: u32,
+    nests: Vec<[f32; 2]>,
+    obstacles: Vec<Obstacle>,
+    initial_food: Vec<FoodPile>,
+}
+
+impl Scenario {
+    /// Reads a `.png` image or, for any other extension, a text map.
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        let source = path.display().to_string();
+        let error = |message: String| ConfigError {
+            source: source.clone(),
+            line: None,
+            key: None,
+            message,
+        };
+        if path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("png"))
+        {
+            let image = image::open(path).map_err(|err| error(err.to_string()))?;
+            Ok(Self::from_image(&image.to_rgba8()))
+        } else {
+            let text = fs::read_to_string(path).map_err(|err| error(err.to_string()))?;
+            Self::parse_map(&source, &text)
+        }
+    }
+
+    /// Black pixels are walls, red ones nests and green ones food, more of it the brighter the
+    /// green. Anything else, including transparent pixels, is open ground.
+    pub fn from_image(image: &RgbaImage) -> Self {
+        let cells = image
+            .pixels()
+            .map(|&Rgba([r, g, b, a])| {
+                if a < 128 {
+                    Cell::Empty
+                } else if r.max(g).max(b) < 64 {
+                    Cell::Wall
+                } else if r >= 128 && g < 64 && b < 64 {
+                    Cell::Nest
+                } else if g >= 64 && r < 64 && b < 64 {
+                    Cell::Food(g as f32 / 255.0)
+                } else {
+                    Cell::Empty
+                }
+            })
+            .collect();
+        Self {
+            cells,
+            width: image.width() as usize,
+            height: image.height() as usize,
+        }
+    }
+
+    /// Each line is a row of cells: `#` is a wall, `N` a nest, `1` to `9` food (more the higher
+    /// the digit) and `.` or a space open ground. Short rows are padded with open ground.
+    pub fn parse_map(source: &str, text: &str) -> Result<Self, ConfigError> {
+        let rows: Vec<&str> = text.lines().collect();
+        let width = rows
+            .iter()
+            .map(|row| row.chars().count())
+            .max()
+            .unwrap_or(0);
+        let height = rows.len();
+        if width == 0 {
+            return Err(ConfigError {
+                source: source.to_string(),
+                line: None,
+                key: None,
+                message: "the map is empty".to_string(),
+            });
+        }
+
+        let mut cells = vec![Cell::Empty; width * height];
+        for (y, row) in rows.iter().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                cells[x + y * width] = match c {
+                    '#' => Cell::Wall,
+                    'N' => Cell::Nest,
+                    '1'..='9' => Cell::Food(c.to_digit(10).unwrap() as f32 / 9.0),
+                    '.' | ' ' => Cell::Empty,
+                    _ => {
+                        return Err(ConfigError {
+                            source: source.to_string(),
+                            line: Some(y + 1),
+                            key: None,
+                            message: format!("unknown map cell `{c}` in column {}", x + 1),
+                        })
+                    }
+                };
+            }
+        }
+        Ok(Self {
+            cells,
+            width,
+            height,
+        })
+    }
+
+    /// The world position of the center of cell `(x, y)`, which may be fractional.
+    fn cell_center(&self, x: f32, y: f32, simulation_config: &SimulationConfig) -> Vec2 {
+        let cell_size = self.cell_size(simulation_config);
+        Vec2::new(
+            (x + 0.5) * cell_size.x - simulation_config.world_width / 2.0,
+            simulation_config.world_height / 2.0 - (y + 0.5) * cell_size.y,
+        )
+    }
+
+    fn cell_size(&self, simulation_config: &SimulationConfig) -> Vec2 {
+        Vec2::new(
+            simulation_config.world_width / self.width as f32,
+            simulation_config.world_height / self.height as f32,
+        )
+    }
+
+    /// One nest at the middle of each group of touching nest cells, in reading order.
+    fn nests(&self, simulation_config: &SimulationConfig) -> Vec<[f32; 2]> {
+        let mut seen = vec![false; self.cells.len()];
+        let mut nests = vec![];
+        for start in 0..self.cells.len() {
+            if seen[start] || self.cells[start] != Cell::Nest {
+                continue;
+            }
+            seen[start] = true;
+            let mut pending = vec![start];
+            let (mut sum, mut count) = (Vec2::ZERO, 0.0);
+            while let Some(i) = pending.pop() {
+                let (x, y) = (i % self.width, i / self.width);
+                sum += Vec2::new(x as f32, y as f32);
+                count += 1.0;
+                let neighbors = [
+                    (x > 0).then(|| i - 1),
+                    (x + 1 < self.width).then(|| i + 1),
+                    (y > 0).then(|| i - self.width),
+                    (y + 1 < self.height).then(|| i + self.width),
+                ];
+                for neighbor in neighbors.into_iter().flatten() {
+                    if !seen[neighbor] && self.cells[neighbor] == Cell::Nest {
+                        seen[neighbor] = true;
+                        pending.push(neighbor);
+                    }
+                }
+            }
+            let middle = sum / count;
+            nests.push(
+                self.cell_center(middle.x, middle.y, simulation_config)
+                    .to_array(),
+            );
+        }
+        nests
+    }
+
+    /// One rectangle per horizontal run of wall cells.
+    fn obstacles(&self, simulation_config: &SimulationConfig) -> Vec<Obstacle> {
+        let cell_size = self.cell_size(simulation_config);
+        let mut obstacles = vec![];
+        for y in 0..self.height {
+            let row = &self.cells[y * self.width..(y + 1) * self.width];
+            let mut x = 0;
+            while x < self.width {
+                if row[x] != Cell::Wall {
+                    x += 1;
+                    continue;
+                }
+                let start = x;
+                while x < self.width && row[x] == Cell::Wall {
+                    x += 1;
+                }
+                let length = (x - start) as f32;
+                obstacles.push(Obstacle::Rectangle {
+                    center: self
+                        .cell_center((start + x - 1) as f32 / 2.0, y as f32, simulation_config)
+                        .to_array(),
+                    size: [length * cell_size.x, cell_size.y],
+                });
+            }
+        }
+        obstacles
+    }
+
+    fn food(&self, simulation_config: &SimulationConfig) -> Vec<FoodPile> {
+        self.cells
+            .iter()
+            .enumerate()
+            .filter_map(|(i, cell)| match *cell {
+                Cell::Food(intensity) => Some(FoodPile {
+                    kind: FoodKind::default(),
+                    position: self
+                        .cell_center(
+                            (i % self.width) as f32,
+                            (i / self.width) as f32,
+                            simulation_config,
+                        )
+                        .to_array(),
+                    amount: intensity * SCENARIO_MAX_FOOD_PER_CELL,
+                }),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// A config layer placing the scenario's nests, walls and food in a world the size
+    /// `simulation_config` describes. The scenario replaces random food, though a later layer
+    /// can add it back.
+    pub fn to_layer(
+        &self,
+        name: String,
+        simulation_config: &SimulationConfig,
+    ) -> Result<ConfigLayer, ConfigError> {
+        let error = |message: String| ConfigError {
+            source: name.clone(),
+            line: None,
+            key: None,
+            message,
+        };
+        let layout = Layout {
+            random_food: 0,
+            nests: self.nests(simulation_config),
+            obstacles: self.obstacles(simulation_config),
+            initial_food: self.food(simulation_config),
+        };
+        if layout.nests.is_empty() {
+            return Err(error("the scenario has no nests".to_string()));
+        }
+        let text = toml::to_string(&layout).map_err(|err| error(err.to_string()))?;
+        Ok(ConfigLayer { name, text })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn world(width: f32, height: f32) -> ConfigLayer {
+        ConfigLayer {
+            name: "world".to_string(),
+            text: format!("world_width = {width}\nworld_height = {height}"),
+        }
+    }
+
+    #[test]
+    fn map_cells_are_placed_over_the_world() {
+        let world = world(400.0, 300.0);
+        let scenario = Scenario::parse_map("map", "####\n.NN.\n.NN9\n").unwrap();
+        let layer = scenario
+            .to_layer(
+                "map".to_string(),
+                &SimulationConfig::from_layers(std::slice::from_ref(&world)).unwrap(),
+            )
+            .unwrap();
+        let config = SimulationConfig::from_layers(&[world, layer])
+            .unwrap_or_else(|errors| panic!("{}", errors[0]));
+
+        assert_eq!(config.nests, vec![[0.0, -50.0]]);
+        assert_eq!(config.random_food, 0);
+        assert_eq!(config.obstacles.len(), 1);
+        let Obstacle::Rectangle { center, size } = config.obstacles[0] else {
+            panic!("walls should become rectangles");
+        };
+        assert_eq!((center, size), ([0.0, 100.0], [400.0, 100.0]));
+        assert_eq!(config.initial_food.len(), 1);
+        assert_eq!(config.initial_food[0].position, [150.0, -100.0]);
+        assert_eq!(config.initial_food[0].amount, SCENARIO_MAX_FOOD_PER_CELL);
+    }
+
+    #[test]
+    fn unknown_map_cells_report_their_line() {
+        let error = Scenario::parse_map("map", "N..\n.x.\n").err().unwrap();
+        assert_eq!(error.line, Some(2));
+    }
+}
@@ -0,0 +1,385 @@
+use std::{
+    fs::{self, File},
+    io::{BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use bevy::{app::AppExit, ecs::system::SystemParam, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ant::{Age, Ant, AntKind, HeldFood, Satiation},
+    combat::{Armour, Attack, Health},
+    config::SimulationConfig,
+    crowding::Crowding,
+    food::{Corpse, Food, FoodSupply},
+    nest::{Brood, Colony, Nest},
+    predator::{Predator, PredatorKind},
+    queen::Flight,
+    rng::SimulationRng,
+    run::SimulationTick,
+    task::Task,
+    track::Tracks,
+};
+
+const SNAPSHOT_MAGIC: [u8; 8] = *b"ANTSNAP\0";
+
+/// Bump whenever the layout of [`WorldSnapshot`] changes so old files are rejected rather than
+/// misread.
+pub const SNAPSHOT_VERSION: u32 = 15;
+
+#[derive(Serialize, Deserialize)]
+struct SnapshotHeader {
+    magic: [u8; 8],
+    version: u32,
+}
+
+/// Everything needed to continue a run exactly where it left off.
+#[derive(Serialize, Deserialize)]
+pub struct WorldSnapshot {
+    /// The config the run was using, as TOML so it can be layered under new overrides.
+    pub config: String,
+    tick: u64,
+    rng: SimulationRng,
+    tracks: Vec<Tracks>,
+    ants: Vec<AntSnapshot>,
+    food: Vec<FoodSnapshot>,
+    food_supply: FoodSupply,
+    nests: Vec<NestSnapshot>,
+    predators: Vec<PredatorSnapshot>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct AntSnapshot {
+    transform: Transform,
+    satiation: Satiation,
+    age: Age,
+    held_food: HeldFood,
+    kind: AntKind,
+    task: Task,
+    colony: Colony,
+    flight: Option<Flight>,
+    health: Health,
+    attack: Attack,
+    armour: Armour,
+    crowding: Crowding,
+}
+
+#[derive(Serialize, Deserialize)]
+struct FoodSnapshot {
+    transform: Transform,
+    food: Food,
+    corpse: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct NestSnapshot {
+    transform: Transform,
+    nest: Nest,
+    brood: Brood,
+    colony: Colony,
+}
+
+#[derive(Serialize, Deserialize)]
+struct PredatorSnapshot {
+    transform: Transform,
+    predator: Predator,
+    kind: PredatorKind,
+    health: Health,
+    armour: Armour,
+}
+
+impl WorldSnapshot {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let describe = |err: bincode::Error| format!("{}: {err}", path.display());
+        let file = File::open(path).map_err(|err| format!("{}: {err}", path.display()))?;
+        let mut reader = BufReader::new(file);
+
+        let header: SnapshotHeader = bincode::deserialize_from(&mut reader).map_err(describe)?;
+        if header.magic != SNAPSHOT_MAGIC {
+            return Err(format!("{}: not a snapshot file", path.display()));
+        }
+        if header.version != SNAPSHOT_VERSION {
+            return Err(format!(
+                "{}: snapshot version {} is not supported, expected version {SNAPSHOT_VERSION}",
+                path.display(),
+                header.version
+            ));
+        }
+        bincode::deserialize_from(&mut reader).map_err(describe)
+    }
+
+    /// Checks that `simulation_config` describes the same world geometry as the snapshot, since
+    /// the saved pheromone fields can't be resized.
+    pub fn check_config(&self, simulation_config: &SimulationConfig) -> Result<(), String> {
+        let world_size = Vec2::new(
+            simulation_config.world_width,
+            simulation_config.world_height,
+        );
+        for tracks in self.tracks.iter() {
+            if tracks.world_size() != world_size
+                || tracks.resolution() != simulation_config.track_resolution
+            {
+                return Err(format!(
+                    "world_width, world_height and track_resolution must match the snapshot \
+                     ({}, {} and {})",
+                    tracks.world_size().x,
+                    tracks.world_size().y,
+                    tracks.resolution()
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let describe = |err: std::io::Error| format!("{}: {err}", path.display());
+
+        // Write next to the destination and rename so a crash never leaves a torn snapshot.
+        let partial_path = path.with_extension("partial");
+        let mut writer = BufWriter::new(File::create(&partial_path).map_err(describe)?);
+        let header = SnapshotHeader {
+            magic: SNAPSHOT_MAGIC,
+            version: SNAPSHOT_VERSION,
+        };
+        bincode::serialize_into(&mut writer, &header)
+            .and_then(|_| bincode::serialize_into(&mut writer, self))
+            .map_err(|err| format!("{}: {err}", path.display()))?;
+        writer.flush().map_err(describe)?;
+        drop(writer);
+        fs::rename(&partial_path, path).map_err(describe)
+    }
+}
+
+/// A snapshot waiting to be spawned into the world in place of the usual setup.
+#[derive(Resource)]
+pub struct PendingSnapshot(pub Option<WorldSnapshot>);
+
+#[derive(Resource)]
+pub struct SnapshotOutput {
+    /// Where to write snapshots; `{tick}` is replaced by the tick count.
+    pub path: String,
+    pub interval: Option<u64>,
+}
+
+impl SnapshotOutput {
+    fn path_at(&self, tick: u64) -> PathBuf {
+        PathBuf::from(self.path.replace("{tick}", &tick.to_string()))
+    }
+}
+
+type AntQueryData = (
+    &'static Transform,
+    &'static Satiation,
+    &'static Age,
+    &'static HeldFood,
+    &'static AntKind,
+    &'static Task,
+    &'static Colony,
+    Option<&'static Flight>,
+    (&'static Health, &'static Attack, &'static Armour),
+    &'static Crowding,
+);
+
+#[derive(SystemParam)]
+pub struct SnapshotSource<'w, 's> {
+    simulation_config: Res<'w, SimulationConfig>,
+    tick: Res<'w, SimulationTick>,
+    rng: Res<'w, SimulationRng>,
+    tracks: Query<'w, 's, &'static Tracks>,
+    ants: Query<'w, 's, AntQueryData, With<Ant>>,
+    food: Query<'w, 's, (&'static Transform, &'static Food, Has<Corpse>)>,
+    food_supply: Res<'w, FoodSupply>,
+    nests: Query<
+        'w,
+        's,
+        (
+            &'static Transform,
+            &'static Nest,
+            &'static Brood,
+            &'static Colony,
+        ),
+    >,
+    predators: Query<
+        'w,
+        's,
+        (
+            &'static Transform,
+            &'static Predator,
+            &'static PredatorKind,
+            &'static Health,
+            &'static Armour,
+        ),
+    >,
+}
+
+impl SnapshotSource<'_, '_> {
+    fn capture(&self) -> Result<WorldSnapshot, String> {
+        Ok(WorldSnapshot {
+            config: toml::to_string(&*self.simulation_config).map_err(|err| err.to_string())?,
+            tick: self.tick.0,
+            rng: self.rng.clone(),
+            tracks: self.tracks.iter().cloned().collect(),
+            ants: self
+                .ants
+                .iter()
+                .map(
+                    |(
+                        transform,
+                        satiation,
+                        age,
+                        held_food,
+                        kind,
+                        task,
+                        colony,
+                        flight,
+                        combat,
+                        crowding,
+                    )| {
+                        let (health, attack, armour) = combat;
+                        AntSnapshot {
+                            transform: *transform,
+                            satiation: satiation.clone(),
+                            age: age.clone(),
+                            held_food: held_food.clone(),
+                            kind: *kind,
+                            task: *task,
+                            colony: *colony,
+                            flight: flight.cloned(),
+                            health: health.clone(),
+                            attack: *attack,
+                            armour: *armour,
+                            crowding: crowding.clone(),
+                        }
+                    },
+                )
+                .collect(),
+            food: self
+                .food
+                .iter()
+                .map(|(transform, food, corpse)| FoodSnapshot {
+                    transform: *transform,
+                    food: food.clone(),
+                    corpse,
+                })
+                .collect(),
+            food_supply: self.food_supply.clone(),
+            nests: self
+                .nests
+                .iter()
+                .map(|(transform, nest, brood, colony)| NestSnapshot {
+                    transform: *transform,
+                    nest: nest.clone(),
+                    brood: brood.clone(),
+                    colony: *colony,
+                })
+                .collect(),
+            predators: self
+                .predators
+                .iter()
+                .map(
+                    |(transform, predator, kind, health, armour)| PredatorSnapshot {
+                        transform: *transform,
+                        predator: predator.clone(),
+                        kind: *kind,
+                        health: health.clone(),
+                        armour: *armour,
+                    },
+                )
+                .collect(),
+        })
+    }
+
+    fn save(&self, output: &SnapshotOutput) {
+        let path = output.path_at(self.tick.0);
+        match self.capture().and_then(|snapshot| snapshot.save(&path)) {
+            Ok(()) => info!(
+                "saved snapshot at tick {} to {}",
+                self.tick.0,
+                path.display()
+            ),
+            Err(err) => error!("failed to save snapshot: {err}"),
+        }
+    }
+}
+
+pub fn restore_snapshot(
+    mut commands: Commands,
+    mut pending: ResMut<PendingSnapshot>,
+    mut tick: ResMut<SimulationTick>,
+    mut rng: ResMut<SimulationRng>,
+) {
+    let Some(snapshot) = pending.0.take() else {
+        return;
+    };
+
+    tick.0 = snapshot.tick;
+    *rng = snapshot.rng;
+    for tracks in snapshot.tracks {
+        commands.spawn((tracks,));
+    }
+    for ant in snapshot.ants {
+        let mut entity = commands.spawn((
+            Ant,
+            ant.satiation,
+            ant.age,
+            ant.health,
+            ant.attack,
+            ant.armour,
+            ant.crowding,
+            ant.held_food,
+            SpatialBundle::from_transform(ant.transform),
+            ant.kind,
+            ant.task,
+            ant.colony,
+        ));
+        if let Some(flight) = ant.flight {
+            entity.insert(flight);
+        }
+    }
+    for food in snapshot.food {
+        let mut entity = commands.spawn((food.food, SpatialBundle::from_transform(food.transform)));
+        if food.corpse {
+            entity.insert(Corpse);
+        }
+    }
+    commands.insert_resource(snapshot.food_supply);
+    for nest in snapshot.nests {
+        commands.spawn((
+            nest.nest,
+            nest.brood,
+            nest.colony,
+            SpatialBundle::from_transform(nest.transform),
+        ));
+    }
+    for predator in snapshot.predators {
+        commands.spawn((
+            predator.predator,
+            predator.kind,
+            predator.health,
+            predator.armour,
+            SpatialBundle::from_transform(predator.transform),
+        ));
+    }
+    info!("restored snapshot at tick {}", tick.0);
+}
+
+/// Saves a checkpoint every `interval` ticks; runs after the tick counter has advanced.
+pub fn save_snapshot_checkpoint(output: Res<SnapshotOutput>, source: SnapshotSource) {
+    let Some(interval) = output.interval else {
+        return;
+    };
+    if source.tick.0.is_multiple_of(interval) {
+        source.save(&output);
+    }
+}
+
+pub fn save_snapshot_on_exit(
+    output: Res<SnapshotOutput>,
+    source: SnapshotSource,
+    mut exits: EventReader<AppExit>,
+) {
+    if exits.read().next().is_some() {
+        source.save(&output);
+    }
+}
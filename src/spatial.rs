@@ -0,0 +1,281 @@
+use std::{collections::HashMap, marker::PhantomData};
+
+use bevy::prelude::*;
+
+use crate::{ant::Ant, config::SimulationConfig, food::Food, nest::Nest};
+
+/// A component whose entities are kept in a [`SpatialIndex`] as circles.
+pub trait Indexed: Component {
+    fn extent(&self, simulation_config: &SimulationConfig) -> f32;
+}
+
+impl Indexed for Food {
+    fn extent(&self, _: &SimulationConfig) -> f32 {
+        self.radius()
+    }
+}
+
+/// Ants are indexed as points.
+impl Indexed for Ant {
+    fn extent(&self, _: &SimulationConfig) -> f32 {
+        0.0
+    }
+}
+
+impl Indexed for Nest {
+    fn extent(&self, simulation_config: &SimulationConfig) -> f32 {
+        simulation_config.nest_radius
+    }
+}
+
+struct Entry {
+    entity: Entity,
+    position: Vec2,
+    radius: f32,
+}
+
+/// A uniform grid over the world holding every `T` entity, so proximity queries only look at
+/// nearby cells instead of every entity.
+#[derive(Resource)]
+pub struct SpatialIndex<T> {
+    cell_size: f32,
+    origin: Vec2,
+    width: usize,
+    height: usize,
+    cells: Vec<Vec<Entry>>,
+    /// The cell each entity is filed under.
+    locations: HashMap<Entity, usize>,
+    /// The largest radius ever indexed, which bounds how far away a touching entity can be.
+    max_radius: f32,
+    marker: PhantomData<T>,
+}
+
+impl<T> SpatialIndex<T> {
+    pub fn from_config(simulation_config: &SimulationConfig) -> Self {
+        Self::new(
+            Vec2::new(
+                simulation_config.world_width,
+                simulation_config.world_height,
+            ),
+            simulation_config.spatial_cell_size,
+        )
+    }
+
+    pub fn new(world_size: Vec2, cell_size: f32) -> Self {
+        let width = (world_size.x / cell_size).ceil().max(1.0) as usize;
+        let height = (world_size.y / cell_size).ceil().max(1.0) as usize;
+        Self {
+            cell_size,
+            origin: -world_size / 2.0,
+            width,
+            height,
+            cells: (0..width * height).map(|_| vec![]).collect(),
+            locations: HashMap::new(),
+            max_radius: 0.0,
+            marker: PhantomData,
+        }
+    }
+
+    /// The cell coordinates containing `position`, clamped to the grid.
+    fn cell_of(&self, position: Vec2) -> (usize, usize) {
+        let cell = ((position - self.origin) / self.cell_size).floor();
+        (
+            (cell.x.max(0.0) as usize).min(self.width - 1),
+            (cell.y.max(0.0) as usize).min(self.height - 1),
+        )
+    }
+
+    pub fn insert(&mut self, entity: Entity, position: Vec2, radius: f32) {
+        self.remove(entity);
+        let (x, y) = self.cell_of(position);
+        let cell = x + y * self.width;
+        self.cells[cell].push(Entry {
+            entity,
+            position,
+            radius,
+        });
+        self.locations.insert(entity, cell);
+        self.max_radius = self.max_radius.max(radius);
+    }
+
+    pub fn remove(&mut self, entity: Entity) {
+        if let Some(cell) = self.locations.remove(&entity) {
+            // Keeps the rest of the cell in order so queries stay deterministic.
+            self.cells[cell].retain(|entry| entry.entity != entity);
+        }
+    }
+
+    /// Every indexed entity whose circle overlaps the circle at `center` with `radius`, with its
+    /// position.
+    pub fn within(&self, center: Vec2, radius: f32) -> impl Iterator<Item = (Entity, Vec2)> + '_ {
+        let reach = radius + self.max_radius;
+        let (minx, miny) = self.cell_of(center - reach);
+        let (maxx, maxy) = self.cell_of(center + reach);
+        (miny..=maxy)
+            .flat_map(move |y| (minx..=maxx).map(move |x| x + y * self.width))
+            .flat_map(move |cell| self.cells[cell].iter())
+            .filter(move |entry| entry.position.distance(center) < radius + entry.radius)
+            .map(|entry| (entry.entity, entry.position))
+    }
+
+    /// The overlapping entity closest to `center` that `accept` allows, ties broken by position
+    /// so the answer doesn't depend on the order entities were indexed in.
+    pub fn nearest_within(
+        &self,
+        center: Vec2,
+        radius: f32,
+        mut accept: impl FnMut(Entity) -> bool,
+    ) -> Option<Entity> {
+        self.within(center, radius)
+            .filter(|(entity, _)| accept(*entity))
+            .min_by(|(_, a), (_, b)| {
+                a.distance_squared(center)
+                    .total_cmp(&b.distance_squared(center))
+                    .then(a.x.total_cmp(&b.x))
+                    .then(a.y.total_cmp(&b.y))
+            })
+            .map(|(entity, _)| entity)
+    }
+}
+
+/// Matches `T` entities that were spawned, moved or resized since the index last saw them.
+type Reindex<T> = Or<(Changed<T>, Changed<Transform>)>;
+
+/// Files newly spawned, moved or resized `T` entities and drops despawned ones.
+pub fn update_spatial_index<T: Indexed>(
+    simulation_config: Res<SimulationConfig>,
+    mut index: ResMut<SpatialIndex<T>>,
+    changed: Query<(Entity, &T, &Transform), Reindex<T>>,
+    mut removed: RemovedComponents<T>,
+) {
+    for entity in removed.read() {
+        index.remove(entity);
+    }
+    for (entity, component, transform) in changed.iter() {
+        index.insert(
+            entity,
+            transform.translation.xy(),
+            component.extent(&simulation_config),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Indexed as a point, like an ant.
+    #[derive(Component)]
+    struct Marker;
+
+    impl Indexed for Marker {
+        fn extent(&self, _: &SimulationConfig) -> f32 {
+            0.0
+        }
+    }
+
+    fn index() -> SpatialIndex<Marker> {
+        SpatialIndex::new(Vec2::new(100.0, 100.0), 10.0)
+    }
+
+    fn found(index: &SpatialIndex<Marker>, center: Vec2, radius: f32) -> Vec<Entity> {
+        let mut entities: Vec<_> = index
+            .within(center, radius)
+            .map(|(entity, _)| entity)
+            .collect();
+        entities.sort();
+        entities
+    }
+
+    #[test]
+    fn queries_reach_across_cell_boundaries() {
+        let mut index = index();
+        let [a, b, c] = [0, 1, 2].map(Entity::from_raw);
+        // Cells are 10 wide from -50, so 0 and 10 are both boundaries.
+        index.insert(a, Vec2::new(-0.5, 0.0), 0.0);
+        index.insert(b, Vec2::new(0.5, 0.0), 0.0);
+        index.insert(c, Vec2::new(10.2, -0.2), 0.0);
+
+        assert_eq!(found(&index, Vec2::new(0.0, 0.0), 1.0), [a, b]);
+        assert_eq!(found(&index, Vec2::new(9.9, 0.1), 0.5), [c]);
+        assert_eq!(found(&index, Vec2::new(5.0, 0.0), 5.5), [b, c]);
+    }
+
+    #[test]
+    fn radius_edges_are_exclusive_and_include_the_entitys_own_radius() {
+        let mut index = index();
+        let [point, circle] = [0, 1].map(Entity::from_raw);
+        index.insert(point, Vec2::new(3.0, 0.0), 0.0);
+        index.insert(circle, Vec2::new(0.0, 4.0), 2.0);
+
+        // The circle's edge is 2 from the origin, the point 3.
+        assert_eq!(found(&index, Vec2::ZERO, 2.0), []);
+        assert_eq!(found(&index, Vec2::ZERO, 2.01), [circle]);
+        assert_eq!(found(&index, Vec2::ZERO, 3.0), [circle]);
+        assert_eq!(found(&index, Vec2::ZERO, 3.01), [point, circle]);
+    }
+
+    #[test]
+    fn nearest_breaks_ties_by_position_whatever_the_insertion_order() {
+        let positions = [
+            Vec2::new(1.0, 0.0),
+            Vec2::new(0.0, 1.0),
+            Vec2::new(0.0, -1.0),
+            Vec2::new(-1.0, 0.0),
+        ];
+        for order in [[0, 1, 2, 3], [3, 2, 1, 0], [1, 3, 0, 2]] {
+            let mut index = index();
+            for i in order {
+                index.insert(Entity::from_raw(i), positions[i as usize], 0.0);
+            }
+            let nearest = index.nearest_within(Vec2::ZERO, 2.0, |_| true);
+            assert_eq!(nearest, Some(Entity::from_raw(3)));
+            let nearest =
+                index.nearest_within(Vec2::ZERO, 2.0, |entity| entity != Entity::from_raw(3));
+            assert_eq!(nearest, Some(Entity::from_raw(2)));
+        }
+    }
+
+    #[test]
+    fn nearest_prefers_the_closer_entity_and_honours_the_radius() {
+        let mut index = index();
+        let [near, far] = [0, 1].map(Entity::from_raw);
+        index.insert(far, Vec2::new(-3.0, 0.0), 0.0);
+        index.insert(near, Vec2::new(0.0, 2.0), 0.0);
+
+        assert_eq!(index.nearest_within(Vec2::ZERO, 5.0, |_| true), Some(near));
+        assert_eq!(index.nearest_within(Vec2::ZERO, 2.0, |_| true), None);
+        assert_eq!(
+            index.nearest_within(Vec2::ZERO, 5.0, |entity| entity == far),
+            Some(far)
+        );
+    }
+
+    #[test]
+    fn moved_and_despawned_entities_are_refiled() {
+        let mut world = World::new();
+        world.insert_resource(SimulationConfig::default());
+        world.insert_resource(index());
+        let mut schedule = Schedule::default();
+        schedule.add_systems(update_spatial_index::<Marker>);
+
+        let marker = world
+            .spawn((Marker, Transform::from_xyz(1.0, 2.0, 0.0)))
+            .id();
+        schedule.run(&mut world);
+        let index = world.resource::<SpatialIndex<Marker>>();
+        assert_eq!(found(index, Vec2::new(1.0, 2.0), 0.5), [marker]);
+
+        world.get_mut::<Transform>(marker).unwrap().translation = Vec3::new(-30.0, 25.0, 0.0);
+        schedule.run(&mut world);
+        let index = world.resource::<SpatialIndex<Marker>>();
+        assert_eq!(found(index, Vec2::new(1.0, 2.0), 0.5), []);
+        assert_eq!(found(index, Vec2::new(-30.0, 25.0), 0.5), [marker]);
+
+        world.despawn(marker);
+        schedule.run(&mut world);
+        let index = world.resource::<SpatialIndex<Marker>>();
+        assert_eq!(found(index, Vec2::new(-30.0, 25.0), 100.0), []);
+        assert!(index.locations.is_empty());
+    }
+}
@@ -0,0 +1,264 @@
+use std::collections::BTreeMap;
+
+use bevy::prelude::*;
+use enum_ordinalize::Ordinalize;
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ant::{Ant, AntKind, HeldFood},
+    config::SimulationConfig,
+    nest::{Brood, Colony, Nest},
+    rng::SimulationRng,
+    track::{Pheromone, Tracks},
+};
+
+/// What an ant is busy with, which decides where it heads.
+#[derive(
+    Component, Ordinalize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Task {
+    /// Wandering away from known trails to find new food.
+    Scout,
+    /// Following trails to food and carrying it home.
+    Forage,
+    /// Idling in the nest, using less energy.
+    Rest,
+    /// Tending the brood, which then develops faster.
+    NestWork,
+    /// Patrolling around the nest and attacking intruders.
+    Defend,
+}
+
+impl Task {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Task::Scout => "scout",
+            Task::Forage => "forage",
+            Task::Rest => "rest",
+            Task::NestWork => "nest_work",
+            Task::Defend => "defend",
+        }
+    }
+
+    /// The task an ant of `kind` starts out with.
+    pub fn initial(kind: AntKind) -> Self {
+        match kind {
+            AntKind::Scout => Task::Scout,
+            AntKind::Worker => Task::Forage,
+            AntKind::Queen => Task::Rest,
+            AntKind::Soldier => Task::Defend,
+        }
+    }
+
+    /// Whether ants doing this task keep to the nest.
+    pub fn in_nest(&self) -> bool {
+        matches!(self, Task::Rest | Task::NestWork)
+    }
+}
+
+/// How strongly an ant responds to a stimulus of strength `stimulus` given its threshold for
+/// the task, from 0 to 1.
+fn response(stimulus: f32, threshold: f32) -> f32 {
+    if stimulus <= 0.0 {
+        return 0.0;
+    }
+    stimulus * stimulus / (stimulus * stimulus + threshold * threshold)
+}
+
+/// What a colony's ants sense of its needs.
+#[derive(Default)]
+struct Stimuli {
+    ants: u32,
+    nurses: u32,
+    /// Ants at the nest carrying food in.
+    returning: u32,
+    food_energy: f32,
+    young: usize,
+}
+
+/// Lets ants quit their tasks for a rest and resting ants take up the task whose stimulus
+/// most exceeds their threshold for it, as the response threshold model has it.
+pub fn allocate_tasks(
+    simulation_config: Res<SimulationConfig>,
+    mut rng: ResMut<SimulationRng>,
+    mut ants: Query<(&mut Task, &AntKind, &HeldFood, &Transform, &Colony), With<Ant>>,
+    nests: Query<(&Nest, &Brood, &Transform, &Colony), Without<Ant>>,
+    tracks: Query<&Tracks>,
+) {
+    let tracks = tracks.single();
+    let task_config = &simulation_config.tasks;
+    let delta_time = simulation_config.fixed_delta_time();
+    let nest_reach = simulation_config.nest_radius * 2.0;
+
+    let mut stimuli: BTreeMap<Colony, Stimuli> = BTreeMap::new();
+    for (nest, brood, _, colony) in nests.iter() {
+        let colony_stimuli = stimuli.entry(*colony).or_default();
+        colony_stimuli.food_energy += nest.food.energy(&simulation_config.food_kinds);
+        colony_stimuli.young += brood.len();
+    }
+    for (task, kind, held_food, transform, colony) in ants.iter() {
+        if !kind.takes_tasks() {
+            continue;
+        }
+        let colony_stimuli = stimuli.entry(*colony).or_default();
+        colony_stimuli.ants += 1;
+        if *task == Task::NestWork {
+            colony_stimuli.nurses += 1;
+        }
+        let at_nest = nests.iter().any(|(_, _, nest_transform, nest_colony)| {
+            nest_colony == colony
+                && nest_transform
+                    .translation
+                    .xy()
+                    .distance(transform.translation.xy())
+                    < nest_reach
+        });
+        if at_nest && !held_food.empty() {
+            colony_stimuli.returning += 1;
+        }
+    }
+
+    // Stimuli for scouting, foraging and nest work, in that order.
+    let strengths: BTreeMap<Colony, [f32; 3]> = stimuli
+        .iter()
+        .map(|(&colony, stimuli)| {
+            let ants = stimuli.ants.max(1) as f32;
+            let need = (1.0 - stimuli.food_energy / (task_config.food_per_ant * ants)).max(0.0);
+            let encounters = stimuli.returning as f32 / ants;
+            let trail = tracks.total(colony)[Pheromone::Food];
+            let trail = trail / (trail + task_config.trail_half);
+            let care =
+                stimuli.young as f32 / (task_config.brood_per_nurse * (stimuli.nurses + 1) as f32);
+            (
+                colony,
+                [
+                    1.0 - trail,
+                    need + task_config.encounter_weight * encounters,
+                    care,
+                ],
+            )
+        })
+        .collect();
+
+    for (mut task, kind, held_food, _, colony) in ants.iter_mut() {
+        // Ants bringing food home finish the trip first.
+        if !kind.takes_tasks() || !held_food.empty() {
+            continue;
+        }
+        let roll = rng.gen::<f32>();
+        if *task != Task::Rest {
+            if roll < task_config.quit_rate * delta_time {
+                *task = Task::Rest;
+            }
+            continue;
+        }
+        let thresholds = &simulation_config.ant_kinds[*kind].thresholds;
+        let strengths = strengths[colony];
+        let mut chance = 0.0;
+        for (candidate, stimulus, threshold) in [
+            (Task::Scout, strengths[0], thresholds.scout),
+            (Task::Forage, strengths[1], thresholds.forage),
+            (Task::NestWork, strengths[2], thresholds.nest_work),
+        ] {
+            chance += response(stimulus, threshold) * task_config.switch_rate * delta_time;
+            if roll < chance {
+                *task = candidate;
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+    use crate::{
+        ant::spawn_ant,
+        config::TaskConfig,
+        food::{FoodKind, FoodStore},
+    };
+
+    /// Runs `allocate_tasks` five times, a second apart, over two colonies of 20 resting
+    /// workers at their nests, which hold `sugar` and `eggs`, and counts what each colony's
+    /// ants end up doing.
+    fn allocate(sugar: [f32; 2], eggs: [usize; 2]) -> [[usize; Task::VARIANT_COUNT]; 2] {
+        let mut world = World::new();
+        let simulation_config = SimulationConfig {
+            ticks_per_second: 1.0,
+            tasks: TaskConfig {
+                switch_rate: 0.2,
+                quit_rate: 0.0,
+                ..default()
+            },
+            ..default()
+        };
+        world.spawn(Tracks::new(Vec2::new(800.0, 200.0), 4.0, 2));
+        world.insert_resource(simulation_config);
+        world.insert_resource(SimulationRng::new(Some(1)));
+        for colony in 0..2 {
+            let x = if colony == 0 { -300.0 } else { 300.0 };
+            let mut food = FoodStore::default();
+            food[FoodKind::Sugar] = sugar[colony];
+            world.spawn((
+                Nest { food },
+                Brood::with_eggs(eggs[colony]),
+                Transform::from_xyz(x, 0.0, 0.0),
+                Colony(colony as u32),
+            ));
+            for _ in 0..20 {
+                let ant = world.run_system_once(
+                    move |mut commands: Commands, simulation_config: Res<SimulationConfig>| {
+                        spawn_ant(
+                            &mut commands,
+                            &simulation_config,
+                            x,
+                            0.0,
+                            0.0,
+                            AntKind::Worker,
+                            Colony(colony as u32),
+                        )
+                    },
+                );
+                *world.get_mut::<Task>(ant).unwrap() = Task::Rest;
+            }
+        }
+
+        for _ in 0..5 {
+            world.run_system_once(allocate_tasks);
+        }
+        let mut counts = [[0; Task::VARIANT_COUNT]; 2];
+        for (task, colony) in world.query::<(&Task, &Colony)>().iter(&world) {
+            counts[colony.0 as usize][task.ordinal() as usize] += 1;
+        }
+        counts
+    }
+
+    #[test]
+    fn response_is_even_at_the_threshold() {
+        assert_eq!(response(0.0, 0.5), 0.0);
+        assert_eq!(response(0.5, 0.5), 0.5);
+        assert!(response(0.1, 0.5) < 0.1);
+        assert!(response(2.0, 0.5) > 0.9);
+        assert_eq!(response(0.3, 0.0), 1.0);
+    }
+
+    #[test]
+    fn hungry_colonies_send_resting_ants_foraging() {
+        let counts = allocate([0.0, 100.0], [0, 0]);
+        let forage = Task::Forage.ordinal() as usize;
+        assert!(counts[0][forage] >= 5, "{counts:?}");
+        assert_eq!(counts[1][forage], 0);
+    }
+
+    #[test]
+    fn large_broods_draw_in_nurses() {
+        let counts = allocate([100.0, 100.0], [30, 0]);
+        let nest_work = Task::NestWork.ordinal() as usize;
+        assert!(counts[0][nest_work] >= 5, "{counts:?}");
+        assert_eq!(counts[1][nest_work], 0);
+    }
+}
@@ -4,92 +4,324 @@ use bevy::{
         render_asset::RenderAssetUsages,
         render_resource::{Extent3d, TextureDimension, TextureFormat},
     },
+    tasks::{ComputeTaskPool, TaskPool},
 };
 
-use crate::config::{
-    SimulationConfig, FIXED_DELTA_TIME, LAYER_TRACK, TRACK_RESOLUTION, WORLD_HEIGHT, WORLD_WIDTH,
+use enum_ordinalize::Ordinalize;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::{SimulationConfig, LAYER_TRACK, OBSTACLE_PIXEL},
+    nest::Colony,
+    obstacle::Obstacles,
 };
 
-pub struct Track {
-    pub food: f32,
-    pub nest: f32,
+/// A kind of pheromone ants lay down and follow; each is configured by a
+/// [`PheromoneChannel`](crate::config::PheromoneChannel).
+#[derive(Ordinalize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Pheromone {
+    /// Laid by ants carrying food, leading back to it.
+    Food,
+    /// Laid by nests and by ants looking for food, leading home.
+    Nest,
+    /// Laid by ants under attack, starving or facing an enemy; draws soldiers and drives
+    /// foragers off.
+    Alarm,
+}
+
+impl Pheromone {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Pheromone::Food => "food",
+            Pheromone::Nest => "nest",
+            Pheromone::Alarm => "alarm",
+        }
+    }
+}
+
+/// The concentration of every pheromone in one cell.
+#[derive(Clone, Default)]
+pub struct Track([f32; Pheromone::VARIANT_COUNT]);
+
+impl std::ops::Index<Pheromone> for Track {
+    type Output = f32;
+
+    fn index(&self, pheromone: Pheromone) -> &Self::Output {
+        &self.0[pheromone.ordinal() as usize]
+    }
 }
 
-#[derive(Component)]
-pub struct Tracks(Vec<Track>);
+impl std::ops::IndexMut<Pheromone> for Track {
+    fn index_mut(&mut self, pheromone: Pheromone) -> &mut Self::Output {
+        &mut self.0[pheromone.ordinal() as usize]
+    }
+}
+
+/// What happens to pheromone that diffuses past the edge of the world.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Boundary {
+    /// It is lost, as if the world were surrounded by empty ground.
+    Absorbing,
+    /// It stays in the edge cell, as if the world were walled in.
+    #[default]
+    Reflecting,
+    /// It comes back in on the opposite edge.
+    Periodic,
+}
+
+impl Boundary {
+    /// The cell that `(x, y)` exchanges pheromone with in direction `(dx, dy)`, or `None` if
+    /// that's outside the world and absorbs it.
+    fn neighbor(
+        self,
+        (x, y): (usize, usize),
+        (dx, dy): (isize, isize),
+        (width, height): (usize, usize),
+    ) -> Option<usize> {
+        let nx = x as isize + dx;
+        let ny = y as isize + dy;
+        let inside = (0..width as isize).contains(&nx) && (0..height as isize).contains(&ny);
+        let (nx, ny) = match self {
+            _ if inside => (nx, ny),
+            Boundary::Absorbing => return None,
+            Boundary::Reflecting => (x as isize, y as isize),
+            Boundary::Periodic => (
+                nx.rem_euclid(width as isize),
+                ny.rem_euclid(height as isize),
+            ),
+        };
+        Some(nx as usize + ny as usize * width)
+    }
+}
+
+/// Pheromone fields over the world, one per colony and pheromone so ants only follow their own
+/// colony's trails. Each field is a separate plane of cells so the per-tick update streams through
+/// contiguous memory.
+#[derive(Component, Clone, Serialize, Deserialize)]
+pub struct Tracks {
+    /// `width * height` cells per colony and pheromone, ordered by colony then pheromone.
+    planes: Vec<Vec<f32>>,
+    /// Where the next update of a plane is written before it's swapped in.
+    #[serde(skip)]
+    scratch: Vec<f32>,
+    world_size: Vec2,
+    resolution: f32,
+    width: usize,
+    height: usize,
+}
 
 impl Tracks {
-    pub fn within_circle(&self, center: Vec2, radius: f32) -> impl Iterator<Item = &Track> {
-        world_pixels_within_circle(center, radius)
-            .map(move |(x, y)| &self.0[x + y * self.width() as usize])
+    pub fn new(world_size: Vec2, resolution: f32, colonies: usize) -> Self {
+        let width = (world_size.x / resolution) as usize;
+        let height = (world_size.y / resolution) as usize;
+        Self {
+            planes: vec![vec![0.0; width * height]; colonies * Pheromone::VARIANT_COUNT],
+            scratch: vec![],
+            world_size,
+            resolution,
+            width,
+            height,
+        }
     }
 
-    pub fn within_circle_mut(&mut self, center: Vec2, radius: f32, mut f: impl FnMut(&mut Track)) {
-        let width = self.width();
-        for (x, y) in world_pixels_within_circle(center, radius) {
-            f(&mut self.0[x + y * width as usize]);
+    fn colony_planes(&self, colony: Colony) -> &[Vec<f32>] {
+        &self.planes[colony.0 as usize * Pheromone::VARIANT_COUNT..][..Pheromone::VARIANT_COUNT]
+    }
+
+    fn plane_mut(&mut self, colony: Colony, pheromone: Pheromone) -> &mut Vec<f32> {
+        &mut self.planes
+            [colony.0 as usize * Pheromone::VARIANT_COUNT + pheromone.ordinal() as usize]
+    }
+
+    pub fn within_circle(
+        &self,
+        colony: Colony,
+        center: Vec2,
+        radius: f32,
+    ) -> impl Iterator<Item = Track> + '_ {
+        let planes = self.colony_planes(colony);
+        self.world_pixels_within_circle(center, radius)
+            .map(move |(x, y)| Track(std::array::from_fn(|p| planes[p][x + y * self.width])))
+    }
+
+    pub fn within_circle_mut(
+        &mut self,
+        colony: Colony,
+        pheromone: Pheromone,
+        center: Vec2,
+        radius: f32,
+        mut f: impl FnMut(&mut f32),
+    ) {
+        let width = self.width;
+        for (x, y) in self.world_pixels_within_circle(center, radius) {
+            f(&mut self.plane_mut(colony, pheromone)[x + y * width]);
         }
     }
 
+    /// Sums each of `colony`'s pheromones over the whole field.
+    pub fn total(&self, colony: Colony) -> Track {
+        let planes = self.colony_planes(colony);
+        Track(std::array::from_fn(|p| planes[p].iter().sum()))
+    }
+
+    /// Decays every cell by `decay` and spreads a `diffusion` fraction of it to each of its four
+    /// neighbours, in one pass per field. Cells are computed from the fields as they were before
+    /// the step, so rows can be split between the compute task pool's threads and the result
+    /// doesn't depend on the order they run in. Obstacles hold no pheromone and reflect it like
+    /// a [`Boundary::Reflecting`] edge.
+    pub fn update(
+        &mut self,
+        decay: [f32; Pheromone::VARIANT_COUNT],
+        diffusion: [f32; Pheromone::VARIANT_COUNT],
+        boundary: Boundary,
+        obstacles: &Obstacles,
+    ) {
+        let blocked = obstacles.blocked_cells();
+        debug_assert_eq!(blocked.len(), self.width * self.height);
+        let pool = ComputeTaskPool::get_or_init(TaskPool::default);
+        let size = (self.width, self.height);
+        let rows_per_task = self.height.div_ceil(pool.thread_num() * 4).max(1);
+        self.scratch.resize(self.width * self.height, 0.0);
+
+        for (i, plane) in self.planes.iter_mut().enumerate() {
+            let p = i % Pheromone::VARIANT_COUNT;
+            let step = FieldStep {
+                field: plane,
+                blocked,
+                size,
+                decay: decay[p],
+                diffusion: diffusion[p],
+                boundary,
+            };
+            pool.scope(|scope| {
+                for (chunk, rows) in self.scratch.chunks_mut(rows_per_task * size.0).enumerate() {
+                    scope.spawn(async move {
+                        for (row, out) in rows.chunks_mut(size.0).enumerate() {
+                            step.row(chunk * rows_per_task + row, out);
+                        }
+                    });
+                }
+            });
+            std::mem::swap(plane, &mut self.scratch);
+        }
+    }
+
+    pub fn colonies(&self) -> impl Iterator<Item = Colony> {
+        (0..(self.planes.len() / Pheromone::VARIANT_COUNT) as u32).map(Colony)
+    }
+
+    /// Adds empty fields for a newly founded colony and returns its id.
+    pub fn add_colony(&mut self) -> Colony {
+        let colony = Colony((self.planes.len() / Pheromone::VARIANT_COUNT) as u32);
+        let cells = self.width * self.height;
+        self.planes
+            .extend((0..Pheromone::VARIANT_COUNT).map(|_| vec![0.0; cells]));
+        colony
+    }
+
     pub fn width(&self) -> usize {
-        (WORLD_WIDTH / TRACK_RESOLUTION) as usize
+        self.width
     }
 
     pub fn height(&self) -> usize {
-        (WORLD_HEIGHT / TRACK_RESOLUTION) as usize
+        self.height
+    }
+
+    pub fn world_size(&self) -> Vec2 {
+        self.world_size
+    }
+
+    pub fn resolution(&self) -> f32 {
+        self.resolution
+    }
+
+    fn world_pixels_within_circle(
+        &self,
+        center: Vec2,
+        radius: f32,
+    ) -> impl Iterator<Item = (usize, usize)> {
+        let width = self.width as f32;
+        let height = self.height as f32;
+        let tracks_radius = radius / self.resolution;
+
+        let cx = (center.x + self.world_size.x / 2.0) / self.resolution;
+        let cy = (-center.y + self.world_size.y / 2.0) / self.resolution;
+
+        let minx = (cx - tracks_radius).max(0.0).floor() as usize;
+        let miny = (cy - tracks_radius).max(0.0).floor() as usize;
+        let maxx = (cx + tracks_radius).min(width - 1.0).floor() as usize;
+        let maxy = (cy + tracks_radius).min(height - 1.0).floor() as usize;
+
+        (minx..=maxx)
+            .flat_map(move |x| (miny..=maxy).map(move |y| (x, y)))
+            .filter(move |(x, y)| {
+                let dx = cx - *x as f32;
+                let dy = cy - *y as f32;
+                dx * dx + dy * dy <= tracks_radius * tracks_radius
+            })
     }
 }
 
-fn world_pixels_within_circle(center: Vec2, radius: f32) -> impl Iterator<Item = (usize, usize)> {
-    let width = WORLD_WIDTH / TRACK_RESOLUTION;
-    let height = WORLD_HEIGHT / TRACK_RESOLUTION;
-    let tracks_radius = radius / TRACK_RESOLUTION;
-
-    let cx = (center.x + WORLD_WIDTH / 2.0) / TRACK_RESOLUTION;
-    let cy = (-center.y + WORLD_HEIGHT / 2.0) / TRACK_RESOLUTION;
-
-    let minx = (cx - tracks_radius).max(0.0).floor() as usize;
-    let miny = (cy - tracks_radius).max(0.0).floor() as usize;
-    let maxx = (cx + tracks_radius).min(width - 1.0).floor() as usize;
-    let maxy = (cy + tracks_radius).min(height - 1.0).floor() as usize;
-
-    (minx..=maxx)
-        .flat_map(move |x| (miny..=maxy).map(move |y| (x, y)))
-        .filter(move |(x, y)| {
-            let dx = cx - *x as f32;
-            let dy = cy - *y as f32;
-            dx * dx + dy * dy <= tracks_radius * tracks_radius
-        })
+/// One tick's update of one field, shared by the tasks that each compute some of its rows.
+#[derive(Clone, Copy)]
+struct FieldStep<'a> {
+    field: &'a [f32],
+    blocked: &'a [bool],
+    size: (usize, usize),
+    decay: f32,
+    diffusion: f32,
+    boundary: Boundary,
 }
 
-impl Default for Tracks {
-    fn default() -> Self {
-        Self(
-            (0..((WORLD_WIDTH / TRACK_RESOLUTION) as usize
-                * (WORLD_HEIGHT / TRACK_RESOLUTION) as usize))
-                .map(|_| Track {
-                    food: 0.0,
-                    nest: 0.0,
-                })
-                .collect(),
-        )
+impl FieldStep<'_> {
+    fn row(&self, y: usize, out: &mut [f32]) {
+        let (width, height) = self.size;
+        let (field, blocked) = (self.field, self.blocked);
+        let keep = 1.0 - 4.0 * self.diffusion;
+        for (x, out) in out.iter_mut().enumerate() {
+            let i = x + y * width;
+            if blocked[i] {
+                *out = 0.0;
+                continue;
+            }
+            let neighbor = |j: usize| if blocked[j] { field[i] } else { field[j] };
+            let neighbors = if x > 0 && x + 1 < width && y > 0 && y + 1 < height {
+                neighbor(i - 1) + neighbor(i + 1) + neighbor(i - width) + neighbor(i + width)
+            } else {
+                [(0, 1), (1, 0), (0, -1), (-1, 0)]
+                    .into_iter()
+                    .filter_map(|direction| self.boundary.neighbor((x, y), direction, self.size))
+                    .map(neighbor)
+                    .sum()
+            };
+            *out = self.decay * (field[i] * keep + self.diffusion * neighbors);
+        }
     }
 }
 
-pub fn setup_tracks(mut commands: Commands) {
-    commands.spawn((Tracks::default(),));
+pub fn setup_tracks(mut commands: Commands, simulation_config: Res<SimulationConfig>) {
+    commands.spawn((Tracks::new(
+        Vec2::new(
+            simulation_config.world_width,
+            simulation_config.world_height,
+        ),
+        simulation_config.track_resolution,
+        simulation_config.nests.len(),
+    ),));
 }
 
 pub fn setup_tracks_renderin(
     mut commands: Commands,
     mut textures: ResMut<Assets<Image>>,
-    tracks: Query<Entity, Added<Tracks>>,
+    tracks: Query<(Entity, &Tracks), Added<Tracks>>,
 ) {
-    for tracks in tracks.iter() {
+    for (entity, tracks) in tracks.iter() {
         let image = Image::new_fill(
             Extent3d {
-                width: (WORLD_WIDTH / TRACK_RESOLUTION) as u32,
-                height: (WORLD_HEIGHT / TRACK_RESOLUTION) as u32,
+                width: tracks.width() as u32,
+                height: tracks.height() as u32,
                 depth_or_array_layers: 1,
             },
             TextureDimension::D2,
@@ -98,51 +330,38 @@ pub fn setup_tracks_renderin(
             RenderAssetUsages::all(),
         );
         let texture = textures.add(image);
-        commands.entity(tracks).insert(SpriteBundle {
+        commands.entity(entity).insert(SpriteBundle {
             texture,
             transform: Transform::from_translation(Vec3::new(0.0, 0.0, LAYER_TRACK))
-                .with_scale(Vec3::new(TRACK_RESOLUTION, TRACK_RESOLUTION, 1.0)),
+                .with_scale(Vec3::new(tracks.resolution(), tracks.resolution(), 1.0)),
             ..default()
         });
     }
 }
 
-pub fn decay_tracks(simulation_config: Res<SimulationConfig>, mut tracks: Query<&mut Tracks>) {
-    let mut tracks = tracks.single_mut();
-
-    for track in tracks.0.iter_mut() {
-        track.food *= simulation_config
-            .track_concentration_factor
-            .powf(FIXED_DELTA_TIME);
-        track.nest *= simulation_config
-            .track_concentration_factor
-            .powf(FIXED_DELTA_TIME);
-    }
-}
-
-pub fn diffuse_tracks(simulation_config: Res<SimulationConfig>, mut tracks: Query<&mut Tracks>) {
-    let mut tracks = tracks.single_mut();
-
-    for x in 1..tracks.width() as usize - 1 {
-        for y in 1..tracks.height() as usize - 1 {
-            let i = x + y * tracks.width() as usize;
-            let track = &tracks.0[i];
-
-            let mut food = track.food * (1.0 - 4.0 * simulation_config.track_diffusion_factor);
-            let mut nest = track.nest * (1.0 - 4.0 * simulation_config.track_diffusion_factor);
-
-            for (dx, dy) in &[(0, 1), (1, 0), (0, -1), (-1, 0)] {
-                let neighbor = &tracks.0[(x as isize + dx) as usize
-                    + (y as isize + dy) as usize * tracks.width() as usize];
-                food += neighbor.food * simulation_config.track_diffusion_factor;
-                nest += neighbor.nest * simulation_config.track_diffusion_factor;
-            }
-            tracks.0[i] = Track { food, nest };
-        }
-    }
+pub fn update_tracks(
+    simulation_config: Res<SimulationConfig>,
+    obstacles: Res<Obstacles>,
+    mut tracks: Query<&mut Tracks>,
+) {
+    let channels = &simulation_config.pheromones;
+    let decay = std::array::from_fn(|i| {
+        channels[Pheromone::VARIANTS[i]]
+            .decay
+            .powf(simulation_config.fixed_delta_time())
+    });
+    let diffusion = std::array::from_fn(|i| channels[Pheromone::VARIANTS[i]].diffusion);
+    tracks.single_mut().update(
+        decay,
+        diffusion,
+        simulation_config.track_boundary,
+        &obstacles,
+    );
 }
 
 pub fn update_tracks_image(
+    simulation_config: Res<SimulationConfig>,
+    obstacles: Res<Obstacles>,
     tracks: Query<(&Tracks, &Handle<Image>)>,
     mut textures: ResMut<Assets<Image>>,
 ) {
@@ -150,37 +369,205 @@ pub fn update_tracks_image(
 
     let image = textures.get_mut(image).unwrap();
 
-    for (i, track) in tracks.0.iter().enumerate() {
+    for i in 0..tracks.width() * tracks.height() {
+        // Colonies' trails are drawn together; only the mix of pheromones is shown.
+        let mut track = Track::default();
+        for (plane, field) in tracks.planes.iter().enumerate() {
+            let value = &mut track.0[plane % Pheromone::VARIANT_COUNT];
+            *value = (*value + field[i]).min(1.0);
+        }
         let pixel = &mut image.data[i * 4..(i + 1) * 4];
 
-        if track.nest < 0.001 && track.food < 0.001 {
-            pixel.copy_from_slice(&[0, 0, 0, 0]);
+        if obstacles.blocked_cells()[i] {
+            pixel.copy_from_slice(&OBSTACLE_PIXEL);
             continue;
-        } else if track.nest < 0.001 {
-            pixel.copy_from_slice(&[0, 255, 0, (track.food * 255.0) as u8]);
-            continue;
-        } else if track.food < 0.001 {
-            pixel.copy_from_slice(&[255, 0, 0, (track.nest * 255.0) as u8]);
+        }
+
+        let strongest = track.0.iter().copied().fold(0.0, f32::max);
+        if strongest < 0.001 {
+            pixel.copy_from_slice(&[0, 0, 0, 0]);
             continue;
         }
 
-        let nest_over_food = track.nest / track.food;
-        let food_over_nest = track.food / track.nest;
-
-        if nest_over_food > 1.0 {
-            pixel.copy_from_slice(&[
-                255,
-                (food_over_nest * 255.0) as u8,
-                0,
-                (track.nest * 255.0) as u8,
-            ]);
-        } else {
-            pixel.copy_from_slice(&[
-                (nest_over_food * 255.0) as u8,
-                255,
-                0,
-                (track.food * 255.0) as u8,
-            ]);
+        // The strongest pheromone shows at full colour, weaker ones blend in by their ratio to it.
+        let mut color = [0.0; 3];
+        for &pheromone in Pheromone::VARIANTS {
+            if track[pheromone] < 0.001 {
+                continue;
+            }
+            let weight = track[pheromone] / strongest;
+            for (channel, value) in color
+                .iter_mut()
+                .zip(simulation_config.pheromones[pheromone].color)
+            {
+                *channel += weight * value as f32;
+            }
+        }
+        let [r, g, b] = color.map(|channel| channel.min(255.0) as u8);
+        pixel.copy_from_slice(&[r, g, b, (strongest * 255.0) as u8]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NO_DECAY: [f32; Pheromone::VARIANT_COUNT] = [1.0; Pheromone::VARIANT_COUNT];
+    const DIFFUSION: [f32; Pheromone::VARIANT_COUNT] = [0.2; Pheromone::VARIANT_COUNT];
+
+    fn no_obstacles(tracks: &Tracks) -> Obstacles {
+        Obstacles::new(tracks.world_size(), tracks.resolution())
+    }
+
+    /// A 10 by 8 cell field with some pheromone against every edge and in the middle.
+    fn tracks() -> Tracks {
+        let mut tracks = Tracks::new(Vec2::new(40.0, 32.0), 4.0, 1);
+        for i in 0..10 * 8 {
+            let (x, y) = (i % 10, i / 10);
+            if x == 0 || y == 0 || x == 9 || y == 7 || (x, y) == (4, 4) {
+                tracks.plane_mut(Colony(0), Pheromone::Food)[i] = 1.0 + i as f32 / 10.0;
+                tracks.plane_mut(Colony(0), Pheromone::Nest)[i] = 0.5;
+            }
+        }
+        tracks
+    }
+
+    fn mass(tracks: &Tracks, pheromone: Pheromone) -> f32 {
+        tracks.total(Colony(0))[pheromone]
+    }
+
+    #[test]
+    fn reflecting_and_periodic_boundaries_conserve_mass() {
+        for boundary in [Boundary::Reflecting, Boundary::Periodic] {
+            let mut tracks = tracks();
+            let obstacles = no_obstacles(&tracks);
+            let before = mass(&tracks, Pheromone::Food);
+            for _ in 0..100 {
+                tracks.update(NO_DECAY, DIFFUSION, boundary, &obstacles);
+            }
+            let after = mass(&tracks, Pheromone::Food);
+            assert!(
+                (after - before).abs() < before * 1e-5,
+                "{before} != {after}"
+            );
+        }
+    }
+
+    #[test]
+    fn absorbing_boundary_only_loses_mass_at_the_edges() {
+        let mut tracks = Tracks::new(Vec2::new(40.0, 32.0), 4.0, 1);
+        let obstacles = no_obstacles(&tracks);
+        tracks.plane_mut(Colony(0), Pheromone::Food)[4 + 4 * 10] = 1.0;
+        tracks.update(NO_DECAY, DIFFUSION, Boundary::Absorbing, &obstacles);
+        assert!((mass(&tracks, Pheromone::Food) - 1.0).abs() < 1e-6);
+
+        let mut tracks = self::tracks();
+        let before = mass(&tracks, Pheromone::Food);
+        tracks.update(NO_DECAY, DIFFUSION, Boundary::Absorbing, &obstacles);
+        assert!(mass(&tracks, Pheromone::Food) < before);
+    }
+
+    #[test]
+    fn decay_scales_mass_during_diffusion() {
+        let mut tracks = tracks();
+        let before = mass(&tracks, Pheromone::Nest);
+        tracks.update(
+            [0.5; Pheromone::VARIANT_COUNT],
+            DIFFUSION,
+            Boundary::Reflecting,
+            &no_obstacles(&tracks),
+        );
+        let after = mass(&tracks, Pheromone::Nest);
+        assert!(
+            (after - before * 0.5).abs() < 1e-5,
+            "{after} != {before} / 2"
+        );
+    }
+
+    #[test]
+    fn diffusion_is_symmetric() {
+        let mut tracks = Tracks::new(Vec2::new(36.0, 36.0), 4.0, 1);
+        let obstacles = no_obstacles(&tracks);
+        tracks.plane_mut(Colony(0), Pheromone::Nest)[4 + 4 * 9] = 1.0;
+        for _ in 0..10 {
+            tracks.update(NO_DECAY, DIFFUSION, Boundary::Reflecting, &obstacles);
+        }
+        let cells = &tracks.colony_planes(Colony(0))[Pheromone::Nest.ordinal() as usize];
+        for y in 0..9 {
+            for x in 0..9 {
+                let value = cells[x + y * 9];
+                for (mx, my) in [(8 - x, y), (x, 8 - y), (y, x)] {
+                    let mirrored = cells[mx + my * 9];
+                    assert!((value - mirrored).abs() < 1e-7, "{value} != {mirrored}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn parallel_update_matches_a_serial_pass() {
+        let mut tracks = Tracks::new(Vec2::new(400.0, 300.0), 4.0, 2);
+        for (i, plane) in tracks.planes.iter_mut().enumerate() {
+            for (j, cell) in plane.iter_mut().enumerate() {
+                *cell = ((i * 31 + j * 17) % 101) as f32 / 101.0;
+            }
         }
+        let mut obstacles = no_obstacles(&tracks);
+        obstacles.set_blocked(50, 30, true);
+        let (width, height) = (tracks.width(), tracks.height());
+        let expected: Vec<Vec<f32>> = tracks
+            .planes
+            .iter()
+            .map(|field| {
+                let step = FieldStep {
+                    field,
+                    blocked: obstacles.blocked_cells(),
+                    size: (width, height),
+                    decay: 0.9,
+                    diffusion: 0.2,
+                    boundary: Boundary::Periodic,
+                };
+                let mut out = vec![0.0; field.len()];
+                for (y, row) in out.chunks_mut(width).enumerate() {
+                    step.row(y, row);
+                }
+                out
+            })
+            .collect();
+
+        tracks.update(
+            [0.9; Pheromone::VARIANT_COUNT],
+            DIFFUSION,
+            Boundary::Periodic,
+            &obstacles,
+        );
+        assert!(tracks.planes == expected);
+    }
+
+    #[test]
+    fn walls_keep_pheromone_on_their_side() {
+        let mut tracks = Tracks::new(Vec2::new(40.0, 32.0), 4.0, 1);
+        let mut obstacles = no_obstacles(&tracks);
+        for y in 0..8 {
+            obstacles.set_blocked(5, y, true);
+        }
+        tracks.plane_mut(Colony(0), Pheromone::Food)[2 + 4 * 10] = 1.0;
+        for _ in 0..100 {
+            tracks.update(NO_DECAY, DIFFUSION, Boundary::Reflecting, &obstacles);
+        }
+        let cells = &tracks.colony_planes(Colony(0))[Pheromone::Food.ordinal() as usize];
+        let left: f32 = (0..10 * 8).filter(|i| i % 10 < 5).map(|i| cells[i]).sum();
+        assert!((left - 1.0).abs() < 1e-5, "{left} != 1");
+        assert!((0..10 * 8).filter(|i| i % 10 >= 5).all(|i| cells[i] == 0.0));
+    }
+
+    #[test]
+    fn founded_colonies_get_empty_fields() {
+        let mut tracks = tracks();
+        let colony = tracks.add_colony();
+        assert!(colony == Colony(1));
+        assert_eq!(tracks.colonies().count(), 2);
+        assert_eq!(tracks.total(colony).0, [0.0; Pheromone::VARIANT_COUNT]);
+        assert!(tracks.total(Colony(0))[Pheromone::Food] > 0.0);
     }
 }
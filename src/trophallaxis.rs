@@ -0,0 +1,229 @@
+use bevy::prelude::*;
+
+use crate::{
+    ant::{Ant, AntKind, HeldFood, Satiation},
+    config::{SimulationConfig, ANT_MAX_ENERGY},
+    nest::Colony,
+    queen::Flight,
+    spatial::SpatialIndex,
+};
+
+/// Energy passed mouth to mouth from one ant to a nestmate.
+#[derive(Event)]
+pub struct FoodShared {
+    pub colony: Colony,
+    pub donor: AntKind,
+    pub recipient: AntKind,
+    pub energy: f32,
+}
+
+type SharerData = (
+    Entity,
+    &'static mut Satiation,
+    &'static mut HeldFood,
+    &'static AntKind,
+    &'static Colony,
+    &'static Transform,
+);
+
+/// Lets every well-fed ant feed the nearest hungry nestmate in reach, from the food it carries
+/// first and then from its own crop down to a reserve. Donors are picked before anyone is fed,
+/// so the outcome doesn't depend on the order ants are visited in.
+pub fn share_food(
+    simulation_config: Res<SimulationConfig>,
+    ant_index: Res<SpatialIndex<Ant>>,
+    mut ants: Query<SharerData, (With<Ant>, Without<Flight>)>,
+    mut exchanges: EventWriter<FoodShared>,
+) {
+    let trophallaxis_config = &simulation_config.trophallaxis;
+    let hungry = trophallaxis_config.hunger * ANT_MAX_ENERGY;
+    let reserve = trophallaxis_config.reserve * ANT_MAX_ENERGY;
+    let most = trophallaxis_config.rate * simulation_config.fixed_delta_time();
+
+    let mut transfers = vec![];
+    for (entity, satiation, held_food, kind, colony, transform) in ants.iter() {
+        // Queens are fed but never feed others.
+        if *kind == AntKind::Queen || satiation.amount() < hungry {
+            continue;
+        }
+        let carried = if held_food.empty() {
+            0.0
+        } else {
+            held_food.amount() * simulation_config.food_kinds[held_food.kind()].energy
+        };
+        let spare = carried + (satiation.amount() - reserve).max(0.0);
+        if spare <= 0.0 {
+            continue;
+        }
+        let recipient = ant_index.nearest_within(
+            transform.translation.xy(),
+            trophallaxis_config.reach,
+            |other| {
+                ants.get(other)
+                    .is_ok_and(|(_, other_satiation, _, _, other_colony, _)| {
+                        other_colony == colony && other_satiation.amount() < hungry
+                    })
+            },
+        );
+        if let Some(recipient) = recipient {
+            transfers.push((entity, recipient, most.min(spare)));
+        }
+    }
+
+    for (donor, recipient, energy) in transfers {
+        let [donor, recipient] = ants.get_many_mut([donor, recipient]).unwrap();
+        let (_, mut donor_satiation, mut held_food, donor_kind, colony, _) = donor;
+        let (_, mut satiation, _, recipient_kind, ..) = recipient;
+        let given = satiation.add(energy);
+        let mut owed = given;
+        if !held_food.empty() {
+            let energy_per_unit = simulation_config.food_kinds[held_food.kind()].energy;
+            owed -= held_food.remove(owed / energy_per_unit) * energy_per_unit;
+        }
+        donor_satiation.remove(owed);
+        exchanges.send(FoodShared {
+            colony: *colony,
+            donor: *donor_kind,
+            recipient: *recipient_kind,
+            energy: given,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+    use crate::{
+        ant::spawn_ant, config::TrophallaxisConfig, food::FoodKind, spatial::update_spatial_index,
+    };
+
+    /// Where an ant stands, its colony, its satiation and the sugar it carries.
+    type AntSpec = (Vec2, u32, f32, f32);
+
+    /// Two donors competing for one hungry ant, a donor with two equally close hungry ants, a
+    /// hungry ant of another colony right next to a donor, a donor carrying sugar with a hungry
+    /// ant beside it, and an ant on its own.
+    const ANTS: [AntSpec; 8] = [
+        (Vec2::new(0.0, 0.0), 0, 1.0, 0.0),
+        (Vec2::new(-4.0, 0.0), 0, 1.0, 0.0),
+        (Vec2::new(-2.0, 0.0), 0, 0.1, 0.0),
+        (Vec2::new(2.0, 0.0), 0, 0.1, 0.0),
+        (Vec2::new(0.0, 1.0), 1, 0.1, 0.0),
+        (Vec2::new(20.0, 0.0), 0, 1.0, 0.5),
+        (Vec2::new(21.0, 0.0), 0, 0.1, 0.0),
+        (Vec2::new(40.0, 0.0), 0, 0.5, 0.0),
+    ];
+
+    fn sharing_world(ants: impl Iterator<Item = AntSpec>) -> (World, Schedule) {
+        let mut world = World::new();
+        let simulation_config = SimulationConfig {
+            trophallaxis: TrophallaxisConfig {
+                // A tenth of full energy a tick.
+                rate: 6.0,
+                ..default()
+            },
+            ..default()
+        };
+        world.insert_resource(SpatialIndex::<Ant>::from_config(&simulation_config));
+        world.insert_resource(simulation_config);
+        world.init_resource::<Events<FoodShared>>();
+        for (position, colony, satiation, sugar) in ants {
+            let ant = world.run_system_once(
+                move |mut commands: Commands, simulation_config: Res<SimulationConfig>| {
+                    spawn_ant(
+                        &mut commands,
+                        &simulation_config,
+                        position.x,
+                        position.y,
+                        0.0,
+                        AntKind::Worker,
+                        Colony(colony),
+                    )
+                },
+            );
+            let mut ant = world.entity_mut(ant);
+            ant.get_mut::<Satiation>()
+                .unwrap()
+                .remove(ANT_MAX_ENERGY - satiation);
+            ant.get_mut::<HeldFood>()
+                .unwrap()
+                .add(FoodKind::Sugar, 1.0, sugar);
+        }
+        let mut schedule = Schedule::default();
+        schedule.add_systems((update_spatial_index::<Ant>, share_food).chain());
+        (world, schedule)
+    }
+
+    /// Each ant's position, satiation and carried food, in order of position.
+    fn ants(world: &mut World) -> Vec<(Vec2, f32, f32)> {
+        let mut ants: Vec<_> = world
+            .query::<(&Transform, &Satiation, &HeldFood)>()
+            .iter(world)
+            .map(|(transform, satiation, held_food)| {
+                (
+                    transform.translation.xy(),
+                    satiation.amount(),
+                    held_food.amount(),
+                )
+            })
+            .collect();
+        ants.sort_by(|(a, ..), (b, ..)| a.x.total_cmp(&b.x).then(a.y.total_cmp(&b.y)));
+        ants
+    }
+
+    /// Energy in the ants' crops and in the sugar they carry.
+    fn total_energy(world: &mut World) -> f32 {
+        ants(world)
+            .iter()
+            .map(|(_, satiation, sugar)| satiation + sugar)
+            .sum()
+    }
+
+    #[test]
+    fn sharing_moves_energy_without_making_or_losing_any() {
+        let (mut world, mut schedule) = sharing_world(ANTS.into_iter());
+        let before = total_energy(&mut world);
+        let hungry_before: f32 = ants(&mut world)
+            .iter()
+            .filter(|(_, satiation, _)| *satiation < 0.4)
+            .map(|(_, satiation, _)| satiation)
+            .sum();
+
+        let mut shared = 0.0;
+        for _ in 0..3 {
+            schedule.run(&mut world);
+            let mut events = world.resource_mut::<Events<FoodShared>>();
+            shared += events.drain().map(|exchange| exchange.energy).sum::<f32>();
+        }
+        assert!((total_energy(&mut world) - before).abs() < 1e-4);
+        assert!(shared > 0.0);
+
+        let after = ants(&mut world);
+        let at = |i: usize| {
+            *after
+                .iter()
+                .find(|(position, ..)| *position == ANTS[i].0)
+                .unwrap()
+        };
+        let fed: f32 = [2, 3, 4, 6].map(|i| at(i).1).iter().sum();
+        assert!((fed - hungry_before - shared).abs() < 1e-4);
+        // The ant of the other colony went unfed, though it stood closest to a donor.
+        assert!((at(4).1 - 0.1).abs() < 1e-5);
+        // The carrier gave from its load before its crop.
+        assert_eq!(at(5).1, 1.0);
+        assert!(at(5).2 < 0.5);
+    }
+
+    #[test]
+    fn sharing_does_not_depend_on_visit_order() {
+        let (mut forward, mut forward_schedule) = sharing_world(ANTS.into_iter());
+        let (mut backward, mut backward_schedule) = sharing_world(ANTS.into_iter().rev());
+        for _ in 0..3 {
+            forward_schedule.run(&mut forward);
+            backward_schedule.run(&mut backward);
+            assert_eq!(ants(&mut forward), ants(&mut backward));
+        }
+    }
+}